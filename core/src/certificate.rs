@@ -0,0 +1,362 @@
+use std::convert::TryFrom;
+use std::net::IpAddr;
+
+use chrono::{DateTime, TimeZone, Utc};
+use x509_parser::der_parser::parse_der;
+use x509_parser::extensions::GeneralName;
+use x509_parser::parse_x509_certificate;
+use x509_parser::x509::SubjectPublicKeyInfo;
+
+use crate::pin;
+
+pub(crate) const OID_RSA: &str = "1.2.840.113549.1.1.1";
+pub(crate) const OID_EC: &str = "1.2.840.10045.2.1";
+pub(crate) const OID_ED25519: &str = "1.3.101.112";
+
+/// Names and sizes of elliptic curves by OID
+const CURVES: &[(&str, &str, u32)] = &[
+    ("1.2.840.10045.3.1.7", "P-256", 256),
+    ("1.3.132.0.34", "P-384", 384),
+    ("1.3.132.0.35", "P-521", 521),
+    ("1.3.132.0.10", "secp256k1", 256),
+];
+
+/// Names of signature algorithms by OID
+const SIGNATURE_ALGORITHMS: &[(&str, &str)] = &[
+    ("1.2.840.113549.1.1.4", "md5WithRSAEncryption"),
+    ("1.2.840.113549.1.1.5", "sha1WithRSAEncryption"),
+    ("1.2.840.113549.1.1.10", "RSASSA-PSS"),
+    ("1.2.840.113549.1.1.11", "sha256WithRSAEncryption"),
+    ("1.2.840.113549.1.1.12", "sha384WithRSAEncryption"),
+    ("1.2.840.113549.1.1.13", "sha512WithRSAEncryption"),
+    ("1.2.840.10045.4.1", "ecdsa-with-SHA1"),
+    ("1.2.840.10045.4.3.2", "ecdsa-with-SHA256"),
+    ("1.2.840.10045.4.3.3", "ecdsa-with-SHA384"),
+    ("1.2.840.10045.4.3.4", "ecdsa-with-SHA512"),
+    ("1.3.101.112", "Ed25519"),
+];
+
+/// Key sizes and signature algorithms below which certificates are considered weak
+#[derive(Clone, Debug, PartialEq)]
+pub struct CryptoPolicy {
+    /// Minimum size of RSA keys in bits, 2048 by default
+    pub min_rsa_bits: u32,
+    /// Minimum size of elliptic curve keys in bits, 256 by default
+    pub min_ec_bits: u32,
+    /// Accept signatures with SHA-1, signatures with MD5 are always weak
+    pub allow_sha1: bool,
+}
+
+impl Default for CryptoPolicy {
+    fn default() -> Self {
+        CryptoPolicy {
+            min_rsa_bits: 2048,
+            min_ec_bits: 256,
+            allow_sha1: false,
+        }
+    }
+}
+
+/// Details of one certificate in the chain presented by server
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CertificateInfo {
+    /// Distinguished name of subject e.g. CN=example.com
+    pub subject: String,
+    /// Distinguished name of issuer
+    pub issuer: String,
+    /// Serial number in hexadecimal separated by colon
+    pub serial: String,
+    /// Start of validity period in seconds since Unix epoch
+    pub not_before: i64,
+    /// End of validity period in seconds since Unix epoch
+    pub not_after: i64,
+    /// Remaining days to the expiration date
+    pub days: i64,
+    /// Common name of subject
+    pub common_name: Option<String>,
+    /// DNS names and IP addresses in Subject Alternative Name extension
+    pub subject_alt_names: Vec<String>,
+    /// Public key algorithm and size e.g. RSA-2048, P-256 or Ed25519
+    pub key_algorithm: String,
+    /// Size of public key in bits, 0 if unknown
+    pub key_bits: u32,
+    /// Algorithm of signature by issuer e.g. sha256WithRSAEncryption
+    pub signature_algorithm: String,
+    /// SHA-256 fingerprint of certificate in hexadecimal separated by colon
+    pub fingerprint: String,
+    /// SHA-256 fingerprint of SubjectPublicKeyInfo in base64
+    pub spki_fingerprint: String,
+}
+
+impl CertificateInfo {
+    /// Parse certificate in DER format, returns None if it is malformed
+    pub(crate) fn parse(der: &[u8], checked_at: &DateTime<Utc>) -> Option<Self> {
+        let (_, cert) = parse_x509_certificate(der).ok()?;
+        let validity = cert.validity();
+        let not_after = validity.not_after.timestamp();
+        let common_name = cert
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map(String::from);
+        let subject_alt_names = match cert.tbs_certificate.subject_alternative_name() {
+            Some((_, san)) => san
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::DNSName(name) => Some(name.to_string()),
+                    GeneralName::IPAddress(bytes) => Self::ip_address(bytes),
+                    _ => None,
+                })
+                .collect(),
+            None => vec![],
+        };
+        let (key_algorithm, key_bits) = Self::key_algorithm(&cert.tbs_certificate.subject_pki);
+        let signature_algorithm = cert.signature_algorithm.algorithm.to_id_string();
+        let signature_algorithm = SIGNATURE_ALGORITHMS
+            .iter()
+            .find(|(oid, _)| *oid == signature_algorithm)
+            .map_or(signature_algorithm, |(_, name)| name.to_string());
+        Some(CertificateInfo {
+            subject: cert.subject().to_string(),
+            issuer: cert.issuer().to_string(),
+            serial: cert.tbs_certificate.raw_serial_as_string(),
+            not_before: validity.not_before.timestamp(),
+            not_after,
+            days: (Utc.timestamp(not_after, 0) - *checked_at).num_days(),
+            common_name,
+            subject_alt_names,
+            key_algorithm,
+            key_bits,
+            signature_algorithm,
+            fingerprint: pin::certificate_fingerprint(der),
+            spki_fingerprint: pin::spki_fingerprint(der).unwrap_or_default(),
+        })
+    }
+
+    /// Name and size in bits of public key, OID is used as name if it is unknown
+    fn key_algorithm(spki: &SubjectPublicKeyInfo) -> (String, u32) {
+        let oid = spki.algorithm.algorithm.to_id_string();
+        match oid.as_str() {
+            OID_RSA => {
+                let bits = Self::rsa_bits(spki.subject_public_key.data).unwrap_or(0);
+                (format!("RSA-{0}", bits), bits)
+            }
+            OID_EC => {
+                let curve = spki
+                    .algorithm
+                    .parameters
+                    .as_ref()
+                    .and_then(|parameters| parameters.as_oid().ok())
+                    .map(|curve| curve.to_id_string());
+                match CURVES
+                    .iter()
+                    .find(|(oid, _, _)| Some(*oid) == curve.as_deref())
+                {
+                    Some((_, name, bits)) => (name.to_string(), *bits),
+                    None => ("EC".to_string(), 0),
+                }
+            }
+            OID_ED25519 => ("Ed25519".to_string(), 256),
+            _ => (oid, 0),
+        }
+    }
+
+    /// Size of modulus of RSA public key in bits
+    fn rsa_bits(der: &[u8]) -> Option<u32> {
+        let (_, key) = parse_der(der).ok()?;
+        let modulus = key.as_sequence().ok()?.first()?.as_slice().ok()?;
+        let modulus = &modulus[modulus.iter().position(|b| *b != 0)?..];
+        Some(modulus.len() as u32 * 8 - modulus[0].leading_zeros())
+    }
+
+    /// Reasons why key or signature of certificate is weak by policy,
+    /// signature of self-signed certificate is not checked
+    pub(crate) fn weaknesses(&self, policy: &CryptoPolicy) -> Vec<String> {
+        let mut weaknesses = vec![];
+        let min_bits = if self.key_algorithm.starts_with("RSA-") {
+            policy.min_rsa_bits
+        } else if CURVES
+            .iter()
+            .any(|(_, name, _)| *name == self.key_algorithm)
+        {
+            policy.min_ec_bits
+        } else {
+            0
+        };
+        if self.key_bits < min_bits {
+            weaknesses.push(format!("{0} key", self.key_algorithm));
+        }
+        let signature = self.signature_algorithm.to_ascii_lowercase();
+        let weak_signature =
+            signature.contains("md5") || (!policy.allow_sha1 && signature.contains("sha1"));
+        if weak_signature && self.subject != self.issuer {
+            weaknesses.push(format!("{0} signature", self.signature_algorithm));
+        }
+        weaknesses
+    }
+
+    fn ip_address(bytes: &[u8]) -> Option<String> {
+        let address = match bytes.len() {
+            4 => IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?),
+            16 => IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?),
+            _ => return None,
+        };
+        Some(address.to_string())
+    }
+
+    /// Whether domain name is covered by Subject Alternative Names,
+    /// common name is ignored like browsers do
+    ///
+    /// ```
+    /// # use hcc::CertificateInfo;
+    /// let certificate = CertificateInfo {
+    ///     subject_alt_names: vec!["*.example.com".to_string()],
+    ///     ..Default::default()
+    /// };
+    /// assert!(certificate.matches("www.example.com"));
+    /// assert!(!certificate.matches("example.com"));
+    /// ```
+    pub fn matches(&self, domain_name: &str) -> bool {
+        self.subject_alt_names
+            .iter()
+            .any(|pattern| Self::matches_pattern(pattern, domain_name))
+    }
+
+    /// Match domain name against DNS name which may contain wildcard,
+    /// wildcard only matches exactly one whole leftmost label
+    fn matches_pattern(pattern: &str, domain_name: &str) -> bool {
+        let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+        let domain_name = domain_name.trim_end_matches('.').to_ascii_lowercase();
+        match pattern.strip_prefix("*.") {
+            Some(parent) => match domain_name.split_once('.') {
+                // wildcard should not match public suffix e.g. *.com
+                Some((label, rest)) => !label.is_empty() && parent.contains('.') && rest == parent,
+                None => false,
+            },
+            None => !pattern.contains('*') && pattern == domain_name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, SubsecRound, Utc};
+
+    use crate::certificate::{CertificateInfo, CryptoPolicy};
+    use crate::fixtures::TestServer;
+
+    #[test]
+    fn test_parse() {
+        let now = Utc::now().round_subsecs(0);
+        let server = TestServer::with_intermediate(now, now + Duration::days(30));
+        let chain: Vec<CertificateInfo> = server
+            .chain()
+            .iter()
+            .map(|certificate| CertificateInfo::parse(certificate.as_ref(), &now).unwrap())
+            .collect();
+        assert_eq!(2, chain.len());
+        assert_eq!("CN=localhost", chain[0].subject);
+        assert_eq!("CN=hcc test intermediate CA", chain[0].issuer);
+        assert_eq!("CN=hcc test intermediate CA", chain[1].subject);
+        assert_eq!("CN=hcc test CA", chain[1].issuer);
+        assert!(!chain[0].serial.is_empty());
+        assert_eq!(now.timestamp(), chain[1].not_before);
+        assert_eq!((now + Duration::days(30)).timestamp(), chain[1].not_after);
+        assert_eq!(30, chain[1].days);
+        assert!(chain[0].days >= 89);
+    }
+
+    #[test]
+    fn test_parse_names() {
+        let now = Utc::now();
+        let server = TestServer::with_name("example.com");
+        let certificate = CertificateInfo::parse(server.chain()[0].as_ref(), &now).unwrap();
+        assert_eq!(Some("example.com".to_string()), certificate.common_name);
+        assert_eq!(
+            vec!["example.com".to_string()],
+            certificate.subject_alt_names
+        );
+        assert!(certificate.matches("example.com"));
+        assert!(certificate.matches("EXAMPLE.com."));
+        assert!(!certificate.matches("localhost"));
+    }
+
+    #[test]
+    fn test_matches_wildcard() {
+        let certificate = CertificateInfo {
+            subject_alt_names: vec!["*.example.com".to_string(), "*.com".to_string()],
+            ..Default::default()
+        };
+        assert!(certificate.matches("www.example.com"));
+        assert!(certificate.matches("WWW.Example.COM"));
+        assert!(!certificate.matches("example.com"));
+        assert!(!certificate.matches("a.b.example.com"));
+        assert!(!certificate.matches("www.example.net"));
+        assert!(!certificate.matches("example.net"));
+
+        let certificate = CertificateInfo {
+            subject_alt_names: vec!["w*.example.com".to_string()],
+            common_name: Some("www.example.com".to_string()),
+            ..Default::default()
+        };
+        assert!(!certificate.matches("www.example.com"));
+    }
+
+    #[test]
+    fn test_parse_algorithms() {
+        let now = Utc::now();
+        let server = TestServer::new();
+        let certificate = CertificateInfo::parse(server.chain()[0].as_ref(), &now).unwrap();
+        assert_eq!("P-256", certificate.key_algorithm);
+        assert_eq!(256, certificate.key_bits);
+        assert_eq!("ecdsa-with-SHA256", certificate.signature_algorithm);
+
+        let server = TestServer::with_rsa_key();
+        let certificate = CertificateInfo::parse(server.chain()[0].as_ref(), &now).unwrap();
+        assert_eq!("RSA-2048", certificate.key_algorithm);
+        assert_eq!(2048, certificate.key_bits);
+
+        let server = TestServer::with_ed25519_key();
+        let certificate = CertificateInfo::parse(server.chain()[0].as_ref(), &now).unwrap();
+        assert_eq!("Ed25519", certificate.key_algorithm);
+    }
+
+    #[test]
+    fn test_weaknesses() {
+        let policy = CryptoPolicy::default();
+        let certificate = CertificateInfo {
+            subject: "CN=example.com".to_string(),
+            key_algorithm: "RSA-1024".to_string(),
+            key_bits: 1024,
+            signature_algorithm: "sha1WithRSAEncryption".to_string(),
+            ..Default::default()
+        };
+        let weaknesses = vec![
+            "RSA-1024 key".to_string(),
+            "sha1WithRSAEncryption signature".to_string(),
+        ];
+        assert_eq!(weaknesses, certificate.weaknesses(&policy));
+
+        let policy = CryptoPolicy {
+            min_rsa_bits: 1024,
+            allow_sha1: true,
+            ..Default::default()
+        };
+        assert!(certificate.weaknesses(&policy).is_empty());
+
+        let certificate = CertificateInfo {
+            key_algorithm: "P-256".to_string(),
+            key_bits: 256,
+            signature_algorithm: "ecdsa-with-SHA256".to_string(),
+            ..Default::default()
+        };
+        assert!(certificate.weaknesses(&CryptoPolicy::default()).is_empty());
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        assert_eq!(None, CertificateInfo::parse(b"malformed", &Utc::now()));
+    }
+}
@@ -1,22 +1,82 @@
-use std::io::Write;
-use std::net::TcpStream;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
 use std::sync::Arc;
 
-use anyhow::Context;
-use chrono::{DateTime, SubsecRound, TimeZone, Utc};
-use futures::{executor, future};
-use rustls::{ClientConfig, Session};
-use x509_parser::parse_x509_certificate;
+use chrono::{DateTime, SubsecRound, Utc};
+use futures::stream::{self, StreamExt};
+use rand::Rng;
+use rustls::{
+    Certificate, ClientConfig, ClientSession, NoClientSessionStorage, OwnedTrustAnchor,
+    ProtocolVersion, RootCertStore, Session, TLSError,
+};
+use tokio::net::{self, TcpStream};
+use tokio::time;
+use tokio_rustls::TlsConnector;
 
-use crate::check_result::{CheckResult, CheckState};
-use std::time::Instant;
+use crate::certificate::{CertificateInfo, CryptoPolicy};
+use crate::check_result::{CheckResult, CheckState, ErrorKind};
+use crate::ct::{self, CtPolicy, RecordingStream};
+use crate::file;
+use crate::pair::{self, PairResult};
+use crate::pin::{self, Pin};
+use crate::protocol::Protocol;
+use crate::revocation::{self, RevocationStatus};
+use crate::roots;
+use crate::scan::{self, ScanPolicy, ScanResult, Suite, SuiteScan, TlsVersion, VersionScan};
+use crate::target::Target;
+use crate::threshold::Threshold;
+use crate::verifier::RecordingVerifier;
+use std::time::{Duration, Instant};
+
+/// Number of targets checked at the same time by default
+const DEFAULT_CONCURRENCY: usize = 16;
+
+/// Delay before the first retry by default
+const DEFAULT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Maximum random delay added to backoff by default
+const DEFAULT_JITTER: Duration = Duration::from_millis(500);
+
+/// Time allowed to establish TCP connection by default
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time allowed for STARTTLS dialog and TLS handshake by default
+const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time allowed to check one target, including DNS lookup, by default
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Placeholder name in handshake with IP address host, reserved by RFC 6761
+const IP_ADDRESS_NAME: &str = "invalid";
 
 /// Client to check SSL certificate
 pub struct CheckClient {
+    all_addresses: bool,
+    attempts: u32,
+    backoff: Duration,
     checked_at: DateTime<Utc>,
+    clock_skew: Duration,
+    concurrency: usize,
     config: Arc<ClientConfig>,
+    connect_timeout: Duration,
+    critical_in_days: Option<i64>,
+    crypto_policy: Option<CryptoPolicy>,
+    ct_policy: Option<CtPolicy>,
     elapsed: bool,
+    expiry_only: bool,
     grace_in_days: i64,
+    handshake_timeout: Duration,
+    jitter: Duration,
+    lifetime_fraction: Option<f64>,
+    pins: HashMap<String, Vec<Pin>>,
+    pkcs12_password: String,
+    query_revocation: bool,
+    resolve: HashMap<String, IpAddr>,
+    scan_policy: ScanPolicy,
+    thresholds: HashMap<String, Threshold>,
+    timeout: Duration,
 }
 
 impl Default for CheckClient {
@@ -26,10 +86,30 @@ impl Default for CheckClient {
             .root_store
             .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
         CheckClient {
+            all_addresses: false,
+            attempts: 1,
+            backoff: DEFAULT_BACKOFF,
             checked_at: Utc::now().round_subsecs(0),
+            clock_skew: Duration::ZERO,
+            concurrency: DEFAULT_CONCURRENCY,
             config: Arc::new(config),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            critical_in_days: None,
+            crypto_policy: None,
+            ct_policy: None,
             elapsed: false,
+            expiry_only: false,
             grace_in_days: 7,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+            jitter: DEFAULT_JITTER,
+            lifetime_fraction: None,
+            pins: HashMap::new(),
+            pkcs12_password: String::new(),
+            query_revocation: false,
+            resolve: HashMap::new(),
+            scan_policy: ScanPolicy::default(),
+            thresholds: HashMap::new(),
+            timeout: DEFAULT_TIMEOUT,
         }
     }
 }
@@ -54,128 +134,1027 @@ impl CheckClient {
         CheckClientBuilder::default()
     }
 
-    /// Check SSL certificate of one domain name
+    /// Check SSL certificate of one target, a domain name with optional protocol and port
     ///
     /// ```
     /// # use hcc::CheckClient;
     /// let client = CheckClient::new();
     /// client.check_certificate("sha512.badssl.com");
+    /// client.check_certificate("sha512.badssl.com:443");
+    /// client.check_certificate("smtp://smtp.gmail.com:587");
     /// ```
     pub async fn check_certificate<'a>(
         &'a self,
-        domain_name: &'a str,
+        target: &'a str,
     ) -> anyhow::Result<CheckResult<'a>> {
-        let dns_name = webpki::DNSNameRef::try_from_ascii_str(domain_name)?;
-        let mut sess = rustls::ClientSession::new(&self.config, dns_name);
-        let mut sock = TcpStream::connect(format!("{0}:443", domain_name))?;
-        let mut tls = rustls::Stream::new(&mut sess, &mut sock);
+        let (result, _) = self.check_leaf(target).await?;
+        Ok(result)
+    }
 
-        let origin = Instant::now();
-        match tls.write(Self::build_http_headers(domain_name).as_bytes()) {
-            Ok(_) => (),
-            Err(_) => return Ok(CheckResult::expired(domain_name, &self.checked_at)),
+    /// Check one target, returns result and leaf certificate presented by server
+    async fn check_leaf<'a>(
+        &'a self,
+        target: &'a str,
+    ) -> anyhow::Result<(CheckResult<'a>, Option<Certificate>)> {
+        let target = Target::parse(target)?;
+        let dns_name = match Self::dns_name(&target) {
+            Some(dns_name) => dns_name,
+            None => {
+                let kind = ErrorKind::InvalidTarget;
+                return Ok((CheckResult::error(&target, kind, &self.checked_at), None));
+            }
         };
-        let elapsed = Instant::now() - origin;
 
-        let certificates = tls
-            .sess
-            .get_peer_certificates()
-            .with_context(|| format!("no peer certificates found for {0}", domain_name))?;
+        let addr = self.resolved_address(&target);
+        Ok(self.check_with_retries(target, dns_name, addr).await)
+    }
 
-        let certificate = certificates
-            .first()
-            .with_context(|| format!("no certificate found for {0}", domain_name))?;
+    /// Check whether private key matches certificate in local file,
+    /// and whether target serves the same certificate when it is given
+    ///
+    /// ```no_run
+    /// # use std::path::Path;
+    /// # use hcc::CheckClient;
+    /// let client = CheckClient::new();
+    /// let (certificate, key) = (Path::new("cert.pem"), Path::new("key.pem"));
+    /// client.verify_pair(certificate, key, "", Some("example.com"));
+    /// ```
+    ///
+    /// See [`verify_pair`](crate::verify_pair) for supported formats.
+    pub async fn verify_pair<'a>(
+        &'a self,
+        certificate: &Path,
+        key: &Path,
+        password: &str,
+        target: Option<&'a str>,
+    ) -> anyhow::Result<PairResult<'a>> {
+        let matched = pair::verify_pair(certificate, key, password)?;
+        let target = match target {
+            Some(target) => target,
+            None => {
+                return Ok(PairResult {
+                    matched,
+                    ..Default::default()
+                })
+            }
+        };
+        let leaf = pair::read_leaf(certificate, password)?;
+        let (served, served_leaf) = self.check_leaf(target).await?;
+        Ok(PairResult {
+            matched,
+            served: Some(served),
+            served_matched: served_leaf.map(|served_leaf| served_leaf.0 == leaf),
+        })
+    }
 
-        let not_after = match parse_x509_certificate(certificate.as_ref()) {
-            Ok((_, cert)) => cert.validity().not_after,
-            Err(_) => return Ok(CheckResult::default()),
+    /// Name given in TLS handshake, IP address hosts are given a placeholder
+    /// which is neither sent as SNI nor verified against certificate
+    fn dns_name<'a>(target: &Target<'a>) -> Option<webpki::DNSNameRef<'a>> {
+        let host = if target.is_ip_address() {
+            IP_ADDRESS_NAME
+        } else {
+            target.host
         };
-        let not_after = Utc.timestamp(not_after.timestamp(), 0);
+        webpki::DNSNameRef::try_from_ascii_str(host).ok()
+    }
 
-        let duration = not_after - self.checked_at;
-        let days = duration.num_days();
-        let state = if days > self.grace_in_days {
-            CheckState::Ok
+    /// Address given by target or by client instead of DNS lookup
+    fn resolved_address(&self, target: &Target) -> Option<SocketAddr> {
+        target
+            .address
+            .or_else(|| self.resolve.get(target.host).copied())
+            .map(|address| SocketAddr::new(address, target.port))
+    }
+
+    /// Check SSL certificate served by every IPv4 and IPv6 address of one target
+    ///
+    /// ```
+    /// # use hcc::CheckClient;
+    /// let client = CheckClient::new();
+    /// client.check_addresses("sha512.badssl.com");
+    /// ```
+    ///
+    /// Each address is checked with the same domain name as SNI and one result
+    /// is returned for each address. Results are marked as inconsistent
+    /// when addresses serve different certificates.
+    pub async fn check_addresses<'a>(
+        &'a self,
+        target: &'a str,
+    ) -> anyhow::Result<Vec<CheckResult<'a>>> {
+        let target = Target::parse(target)?;
+        let dns_name = match Self::dns_name(&target) {
+            Some(dns_name) => dns_name,
+            None => {
+                let kind = ErrorKind::InvalidTarget;
+                return Ok(vec![CheckResult::error(&target, kind, &self.checked_at)]);
+            }
+        };
+
+        let lookup = net::lookup_host((target.host, target.port));
+        let mut addrs: Vec<SocketAddr> = match self.resolved_address(&target) {
+            Some(addr) => vec![addr],
+            None => match time::timeout(self.timeout, lookup).await {
+                Ok(Ok(addrs)) => addrs.collect(),
+                Ok(Err(_)) => {
+                    let kind = ErrorKind::Dns;
+                    return Ok(vec![CheckResult::error(&target, kind, &self.checked_at)]);
+                }
+                Err(_) => {
+                    let kind = ErrorKind::Timeout;
+                    return Ok(vec![CheckResult::error(&target, kind, &self.checked_at)]);
+                }
+            },
+        };
+        // resolver may return the same address more than once, not necessarily adjacent
+        let mut seen = HashSet::new();
+        addrs.retain(|addr| seen.insert(*addr));
+
+        let mut checked: Vec<_> = stream::iter(addrs)
+            .map(|addr| self.check_with_retries(target, dns_name, Some(addr)))
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+        Self::mark_inconsistent(&mut checked);
+        Ok(checked.into_iter().map(|(result, _)| result).collect())
+    }
+
+    /// Mark results as inconsistent when more than one distinct certificate is served
+    fn mark_inconsistent(checked: &mut [(CheckResult, Option<Certificate>)]) {
+        let mut leaves = checked.iter().filter_map(|(_, leaf)| leaf.as_ref());
+        let inconsistent = match leaves.next() {
+            Some(first) => leaves.any(|leaf| leaf != first),
+            None => false,
+        };
+        for (result, leaf) in checked.iter_mut() {
+            result.inconsistent = inconsistent && leaf.is_some();
+        }
+    }
+
+    /// Check target until it succeeds or fails with error which is not retryable,
+    /// returns result and certificate presented by server
+    async fn check_with_retries<'a>(
+        &'a self,
+        target: Target<'a>,
+        dns_name: webpki::DNSNameRef<'a>,
+        addr: Option<SocketAddr>,
+    ) -> (CheckResult<'a>, Option<Certificate>) {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let check = self.check_target(target, dns_name, addr);
+            let (mut result, leaf) = match time::timeout(self.timeout, check).await {
+                Ok(checked) => checked,
+                Err(_) => {
+                    let kind = ErrorKind::Timeout;
+                    (CheckResult::error(&target, kind, &self.checked_at), None)
+                }
+            };
+            let retryable = matches!(result.state, CheckState::Error(kind) if kind.is_retryable());
+            if !retryable || attempts >= self.attempts {
+                result.address = addr.map(|addr| addr.ip());
+                result.attempts = attempts;
+                return (result, leaf);
+            }
+            time::sleep(self.backoff_delay(attempts)).await;
+        }
+    }
+
+    /// Delay before next attempt, doubled after each attempt with random jitter
+    fn backoff_delay(&self, attempts: u32) -> Duration {
+        let factor = 1u32 << (attempts - 1).min(16);
+        let jitter = self.jitter.as_millis() as u64;
+        let jitter = if jitter > 0 {
+            Duration::from_millis(rand::thread_rng().gen_range(0..=jitter))
         } else {
-            CheckState::Warning
+            Duration::ZERO
+        };
+        self.backoff * factor + jitter
+    }
+
+    async fn check_target<'a>(
+        &'a self,
+        target: Target<'a>,
+        dns_name: webpki::DNSNameRef<'a>,
+        addr: Option<SocketAddr>,
+    ) -> (CheckResult<'a>, Option<Certificate>) {
+        let Target {
+            protocol,
+            host: domain_name,
+            port,
+            ..
+        } = target;
+        let (sock, deadline) = match self.connect(&target, addr).await {
+            Ok(connected) => connected,
+            Err(kind) => return (self.error(&target, kind), None),
+        };
+
+        let verifier = Arc::new(if target.is_ip_address() {
+            RecordingVerifier::for_ip_address(self.clock_skew)
+        } else {
+            RecordingVerifier::new(self.clock_skew, self.expiry_only)
+        });
+        let mut config = ClientConfig::clone(&self.config);
+        config.enable_sni = !target.is_ip_address();
+        config
+            .dangerous()
+            .set_certificate_verifier(verifier.clone());
+        // resumed session skips verifier, so every address and attempt is verified afresh
+        config.session_persistence = Arc::new(NoClientSessionStorage {});
+        config.enable_tickets = false;
+        if self.ct_policy.is_some() {
+            // SCTs are requested, and verified later with logs in log list
+            config.ct_logs = Some(&[]);
+        }
+        let connector = TlsConnector::from(Arc::new(config));
+        let sock = RecordingStream::new(sock, self.ct_policy.is_some());
+
+        let origin = Instant::now();
+        let handshake = match time::timeout_at(deadline, connector.connect(dns_name, sock)).await {
+            Ok(handshake) => handshake,
+            Err(_) => Err(io::Error::from(io::ErrorKind::TimedOut)),
+        };
+        let elapsed = Instant::now() - origin;
+
+        let (handshake, certificates, negotiated, received) = match handshake {
+            Ok(stream) => {
+                let (sock, session) = stream.get_ref();
+                let received = sock.received().to_vec();
+                (Ok(()), verifier.take_chain(), negotiated(session), received)
+            }
+            Err(e) => (Err(e), verifier.take_chain(), Default::default(), vec![]),
+        };
+        let (tls_version, cipher_suite, alpn) = negotiated;
+        let stapled = verifier.take_ocsp_response();
+        let revocation = match handshake {
+            Ok(_) => self.revocation(&certificates, &stapled).await,
+            Err(_) => None,
+        };
+        let scts = match (&self.ct_policy, &handshake) {
+            (Some(policy), Ok(_)) => {
+                let now = self.checked_at.timestamp();
+                ct::scts(&certificates, &received, &stapled, &policy.log_list, now)
+            }
+            _ => vec![],
+        };
+        // chain is discarded when any certificate is malformed
+        let chain: Vec<CertificateInfo> = certificates
+            .iter()
+            .map(|certificate| CertificateInfo::parse(certificate.as_ref(), &self.checked_at))
+            .collect::<Option<_>>()
+            .unwrap_or_default();
+        // state is driven by whichever certificate in the chain expires first
+        let earliest = chain.iter().min_by_key(|certificate| certificate.not_after);
+        let days = earliest.map_or(0, |certificate| certificate.days);
+
+        let untrusted = verifier.take_error().map(|e| Self::untrusted_kind(&e));
+
+        let state = match (handshake, earliest) {
+            // path building reports expired intermediate as untrusted issuer
+            (Err(e), _) => match Self::handshake_error_state(&e) {
+                CheckState::Invalid(ErrorKind::UntrustedIssuer)
+                    if self.date_state(&chain, domain_name, port) == CheckState::Expired =>
+                {
+                    CheckState::Expired
+                }
+                state => state,
+            },
+            (Ok(_), None) if certificates.is_empty() => CheckState::Error(ErrorKind::Handshake),
+            (Ok(_), None) => CheckState::Invalid(ErrorKind::BadCertificate),
+            // verifier already rejects expired certificates unless only expiry is checked,
+            // so this mostly decides between ok, warning and critical
+            (Ok(_), Some(_)) => self.date_state(&chain, domain_name, port),
+        };
+        let weak_crypto = self.weak_crypto(&chain);
+        let ct_violation = match (&self.ct_policy, state) {
+            (Some(policy), CheckState::Ok | CheckState::Warning | CheckState::Critical) => {
+                policy.violation(&scts)
+            }
+            _ => None,
         };
-        Ok(CheckResult {
+        let pinned = match Self::for_target(&self.pins, domain_name, port) {
+            Some(pins) => pin::matches_any(pins, &chain),
+            None => true,
+        };
+        let state = match state {
+            CheckState::Ok | CheckState::Warning | CheckState::Critical
+                if revocation == Some(RevocationStatus::Revoked) =>
+            {
+                CheckState::Invalid(ErrorKind::Revoked)
+            }
+            CheckState::Ok | CheckState::Warning | CheckState::Critical if !pinned => {
+                CheckState::PinMismatch
+            }
+            CheckState::Ok if !weak_crypto.is_empty() || ct_violation.is_some() => {
+                CheckState::Warning
+            }
+            state => state,
+        };
+        let result = CheckResult {
             state,
             checked_at: self.checked_at.timestamp(),
-            days: duration.num_days(),
+            days,
+            protocol,
             domain_name,
-            not_after: not_after.timestamp(),
+            port,
+            not_before: chain.first().map_or(0, |leaf| leaf.not_before),
+            not_after: earliest.map_or(0, |certificate| certificate.not_after),
             elapsed: if self.elapsed {
                 Some(elapsed.as_millis())
             } else {
                 None
             },
-        })
+            attempts: 1,
+            name_matched: chain.first().map(|leaf| leaf.matches(domain_name)),
+            chain,
+            untrusted,
+            weak_crypto,
+            tls_version,
+            cipher_suite,
+            alpn,
+            revocation,
+            scts,
+            ct_violation,
+            ..Default::default()
+        };
+        (result, certificates.into_iter().next())
+    }
+
+    /// Connect to target and upgrade connection with STARTTLS,
+    /// returns socket and deadline of TLS handshake
+    async fn connect(
+        &self,
+        target: &Target<'_>,
+        addr: Option<SocketAddr>,
+    ) -> Result<(TcpStream, time::Instant), ErrorKind> {
+        let addrs: Vec<SocketAddr> = match addr {
+            Some(addr) => vec![addr],
+            None => match net::lookup_host((target.host, target.port)).await {
+                Ok(addrs) => addrs.collect(),
+                Err(_) => return Err(ErrorKind::Dns),
+            },
+        };
+        let connect = TcpStream::connect(addrs.as_slice());
+        let mut sock = match time::timeout(self.connect_timeout, connect).await {
+            Ok(Ok(sock)) => sock,
+            Ok(Err(e)) => return Err(Self::io_error_kind(&e)),
+            Err(_) => return Err(ErrorKind::Timeout),
+        };
+
+        // STARTTLS dialog and TLS handshake share one deadline
+        let deadline = time::Instant::now() + self.handshake_timeout;
+        let starttls = target.protocol.starttls(target.host, &mut sock);
+        match time::timeout_at(deadline, starttls).await {
+            Ok(Ok(())) => Ok((sock, deadline)),
+            Ok(Err(_)) => Err(ErrorKind::Starttls),
+            Err(_) => Err(ErrorKind::Timeout),
+        }
+    }
+
+    /// Revocation status of leaf certificate from OCSP response stapled by server,
+    /// then from OCSP responders and CRL distribution points of certificate when querying
+    /// is enabled. None if server does not staple and querying is disabled
+    async fn revocation(
+        &self,
+        certificates: &[Certificate],
+        stapled: &[u8],
+    ) -> Option<RevocationStatus> {
+        if stapled.is_empty() && !self.query_revocation {
+            return None;
+        }
+        // responses are signed by issuer, which must be presented by server and
+        // is searched for, as any certificate can be placed after leaf
+        let (leaf, rest) = match certificates.split_first() {
+            Some((leaf, rest)) => (leaf.as_ref(), rest),
+            None => return Some(RevocationStatus::Unknown),
+        };
+        let issuer = match revocation::find_issuer(leaf, rest.iter().map(|c| c.as_ref())) {
+            Some(issuer) => issuer,
+            None => return Some(RevocationStatus::Unknown),
+        };
+        let now = self.checked_at.timestamp();
+        if !stapled.is_empty() {
+            let status = revocation::ocsp_status(stapled, leaf, issuer, now);
+            if status != RevocationStatus::Unknown || !self.query_revocation {
+                return Some(status);
+            }
+        }
+        if let Some(request) = revocation::ocsp_request(leaf, issuer) {
+            for url in revocation::ocsp_urls(leaf) {
+                let fetch = revocation::fetch(&url, Some(&request));
+                if let Ok(Ok(response)) = time::timeout(self.handshake_timeout, fetch).await {
+                    let status = revocation::ocsp_status(&response, leaf, issuer, now);
+                    if status != RevocationStatus::Unknown {
+                        return Some(status);
+                    }
+                }
+            }
+        }
+        for url in revocation::crl_urls(leaf) {
+            let fetch = revocation::fetch(&url, None);
+            if let Ok(Ok(crl)) = time::timeout(self.handshake_timeout, fetch).await {
+                let status = revocation::crl_status(&crl, leaf, issuer, now);
+                if status != RevocationStatus::Unknown {
+                    return Some(status);
+                }
+            }
+        }
+        Some(RevocationStatus::Unknown)
+    }
+
+    /// Value configured for host and port, or else for host on every port
+    fn for_target<'m, T>(map: &'m HashMap<String, T>, host: &str, port: u16) -> Option<&'m T> {
+        let key = if host.contains(':') {
+            format!("[{0}]:{1}", host, port)
+        } else {
+            format!("{0}:{1}", host, port)
+        };
+        map.get(&key).or_else(|| map.get(host))
     }
 
-    /// Check SSL certificates of multiple domain names
+    /// Threshold for host and port, or grace period combined with lifetime fraction
+    fn threshold(&self, host: &str, port: u16) -> Threshold {
+        match Self::for_target(&self.thresholds, host, port) {
+            Some(threshold) => *threshold,
+            None => Threshold {
+                days: Some(self.grace_in_days),
+                lifetime_fraction: self.lifetime_fraction,
+            },
+        }
+    }
+
+    /// State decided by dates of whichever certificate in chain expires first
+    fn date_state(&self, chain: &[CertificateInfo], host: &str, port: u16) -> CheckState {
+        let earliest = match chain.iter().min_by_key(|certificate| certificate.not_after) {
+            Some(earliest) => earliest,
+            None => return CheckState::Unknown,
+        };
+        let now = self.checked_at.timestamp();
+        if earliest.not_after < now {
+            CheckState::Expired
+        } else if chain[0].not_before > now + self.clock_skew.as_secs() as i64 {
+            CheckState::NotYetValid
+        } else if matches!(self.critical_in_days, Some(days) if earliest.days <= days) {
+            CheckState::Critical
+        } else if self.threshold(host, port).is_reached(earliest, now) {
+            CheckState::Warning
+        } else {
+            CheckState::Ok
+        }
+    }
+
+    /// Reasons why keys or signatures in chain are weak, empty if there is no policy
+    fn weak_crypto(&self, chain: &[CertificateInfo]) -> Vec<String> {
+        let policy = match self.crypto_policy {
+            Some(ref policy) => policy,
+            None => return vec![],
+        };
+        let mut weak_crypto = vec![];
+        for (i, certificate) in chain.iter().enumerate() {
+            for weakness in certificate.weaknesses(policy) {
+                if i == 0 {
+                    weak_crypto.push(weakness);
+                } else {
+                    weak_crypto.push(format!("{0} of {1}", weakness, certificate.subject));
+                }
+            }
+        }
+        weak_crypto
+    }
+
+    fn error<'a>(&'a self, target: &Target<'a>, kind: ErrorKind) -> CheckResult<'a> {
+        CheckResult::error(target, kind, &self.checked_at)
+    }
+
+    fn io_error_kind(e: &io::Error) -> ErrorKind {
+        match e.kind() {
+            io::ErrorKind::ConnectionRefused => ErrorKind::ConnectionRefused,
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => ErrorKind::Timeout,
+            _ => ErrorKind::Connection,
+        }
+    }
+
+    /// Reason why certificate is rejected for reason other than dates
+    fn untrusted_kind(e: &TLSError) -> ErrorKind {
+        match e {
+            TLSError::WebPKIError(webpki::Error::UnknownIssuer) => ErrorKind::UntrustedIssuer,
+            TLSError::WebPKIError(webpki::Error::CertNotValidForName) => ErrorKind::NameMismatch,
+            _ => ErrorKind::BadCertificate,
+        }
+    }
+
+    fn handshake_error_state(e: &io::Error) -> CheckState {
+        let tls_error = e.get_ref().and_then(|e| e.downcast_ref::<TLSError>());
+        match tls_error {
+            Some(TLSError::WebPKIError(e)) => match e {
+                webpki::Error::CertExpired => CheckState::Expired,
+                webpki::Error::CertNotValidYet => CheckState::NotYetValid,
+                _ => CheckState::Invalid(Self::untrusted_kind(tls_error.unwrap())),
+            },
+            Some(TLSError::AlertReceived(_)) => CheckState::Error(ErrorKind::HandshakeAlert),
+            Some(_) => CheckState::Error(ErrorKind::Handshake),
+            None => CheckState::Error(Self::io_error_kind(e)),
+        }
+    }
+
+    /// Check certificate in local file, a PEM bundle, DER file or PKCS#12 archive
     ///
     /// ```
     /// # use hcc::CheckClient;
     /// let client = CheckClient::new();
-    /// client.check_certificates(&["sha256.badssl.com", "sha256.badssl.com"]);
+    /// client.check_file("fullchain.pem");
     /// ```
-    pub fn check_certificates<'a>(
-        &'a self,
-        domain_names: &'a [&str],
-    ) -> anyhow::Result<Vec<CheckResult<'a>>> {
-        let client = Arc::new(self);
+    ///
+    /// Certificates in file are treated like a chain, leaf certificate first,
+    /// and state is decided by dates only since there is no domain name to verify.
+    pub fn check_file<'a>(&'a self, path: &'a str) -> CheckResult<'a> {
+        let origin = Instant::now();
+        let mut result = CheckResult {
+            checked_at: self.checked_at.timestamp(),
+            protocol: Protocol::File,
+            domain_name: path,
+            attempts: 1,
+            ..Default::default()
+        };
+        let certificates = match file::read_certificates(Path::new(path), &self.pkcs12_password) {
+            Ok(certificates) => certificates,
+            Err(ErrorKind::BadCertificate) => {
+                result.state = CheckState::Invalid(ErrorKind::BadCertificate);
+                return result;
+            }
+            Err(kind) => {
+                result.state = CheckState::Error(kind);
+                return result;
+            }
+        };
+        let chain: Option<Vec<CertificateInfo>> = certificates
+            .iter()
+            .map(|certificate| CertificateInfo::parse(certificate, &self.checked_at))
+            .collect();
+        let chain = match chain {
+            Some(chain) => chain,
+            None => {
+                result.state = CheckState::Invalid(ErrorKind::BadCertificate);
+                return result;
+            }
+        };
+        let earliest = chain.iter().min_by_key(|certificate| certificate.not_after);
+        result.weak_crypto = self.weak_crypto(&chain);
+        result.state = match self.date_state(&chain, path, 0) {
+            CheckState::Ok if !result.weak_crypto.is_empty() => CheckState::Warning,
+            state => state,
+        };
+        result.days = earliest.map_or(0, |certificate| certificate.days);
+        result.not_before = chain.first().map_or(0, |leaf| leaf.not_before);
+        result.not_after = earliest.map_or(0, |certificate| certificate.not_after);
+        if self.elapsed {
+            result.elapsed = Some((Instant::now() - origin).as_millis());
+        }
+        result.chain = chain;
+        result
+    }
 
-        let mut futs = vec![];
-        for domain_name in domain_names {
-            let client = client.clone();
-            futs.push(client.check_certificate(domain_name));
+    /// Check certificates in local files, see [`find_files`](crate::find_files)
+    /// to expand glob patterns and directories
+    ///
+    /// ```
+    /// # use hcc::CheckClient;
+    /// let client = CheckClient::new();
+    /// client.check_files(&["cert.pem", "cert.der", "cert.p12"]);
+    /// ```
+    pub fn check_files<'a>(&'a self, paths: &'a [&str]) -> Vec<CheckResult<'a>> {
+        paths.iter().map(|path| self.check_file(path)).collect()
+    }
+
+    /// Check SSL certificates of multiple targets
+    ///
+    /// ```
+    /// # use hcc::CheckClient;
+    /// let client = CheckClient::new();
+    /// client.check_certificates(&["sha256.badssl.com", "sha256.badssl.com:443"]);
+    /// ```
+    ///
+    /// One result is returned for each target in the same order,
+    /// or one result for each address of each target in all addresses mode,
+    /// and a target which cannot be checked is reported as an error result.
+    /// At most `concurrency` targets are checked at the same time.
+    pub async fn check_certificates<'a>(&'a self, targets: &'a [&str]) -> Vec<CheckResult<'a>> {
+        if self.all_addresses {
+            let results: Vec<Vec<CheckResult>> = stream::iter(targets)
+                .map(|target| async move {
+                    match self.check_addresses(target).await {
+                        Ok(results) => results,
+                        Err(_) => vec![CheckResult::invalid_target(target, &self.checked_at)],
+                    }
+                })
+                .buffered(self.concurrency)
+                .collect()
+                .await;
+            return results.into_iter().flatten().collect();
         }
+        stream::iter(targets)
+            .map(|target| async move {
+                match self.check_certificate(target).await {
+                    Ok(result) => result,
+                    Err(_) => CheckResult::invalid_target(target, &self.checked_at),
+                }
+            })
+            .buffered(self.concurrency)
+            .collect()
+            .await
+    }
+}
 
-        let resolved = executor::block_on(future::join_all(futs));
-        let mut results = vec![];
-        for result in resolved {
-            let result = result?;
-            results.push(result);
+impl CheckClient {
+    /// Scan protocol versions and cipher suites accepted by one target,
+    /// and compare them with scan policy
+    ///
+    /// ```
+    /// # use hcc::CheckClient;
+    /// let client = CheckClient::new();
+    /// client.scan("sha512.badssl.com");
+    /// ```
+    ///
+    /// Each version is offered in a separate handshake, then each cipher suite
+    /// of accepted versions. Versions and cipher suites which rustls cannot
+    /// negotiate, e.g. TLS 1.0 or CBC suites, are probed with bare ClientHello.
+    pub async fn scan<'a>(&'a self, target: &'a str) -> anyhow::Result<ScanResult<'a>> {
+        let target = Target::parse(target)?;
+        let mut result = ScanResult::new(&target, &self.scan_policy);
+        let dns_name = match Self::dns_name(&target) {
+            Some(dns_name) => dns_name,
+            None => {
+                result.error = Some(ErrorKind::InvalidTarget);
+                return Ok(result);
+            }
+        };
+        let addr = self.resolved_address(&target);
+        match self.scan_versions(&target, dns_name, addr).await {
+            Ok(versions) => result.complete(versions, &self.scan_policy),
+            Err(kind) => result.error = Some(kind),
         }
-        Ok(results)
+        Ok(result)
     }
 
-    fn build_http_headers(domain_name: &str) -> String {
-        format!(
-            concat!(
-                "GET / HTTP/1.1\r\n",
-                "Host: {0}\r\n",
-                "Connection: close\r\n",
-                "Accept-Encoding: identity\r\n",
-                "\r\n"
-            ),
-            domain_name
-        )
+    async fn scan_versions(
+        &self,
+        target: &Target<'_>,
+        dns_name: webpki::DNSNameRef<'_>,
+        addr: Option<SocketAddr>,
+    ) -> Result<Vec<VersionScan>, ErrorKind> {
+        let mut versions = vec![];
+        for version in TlsVersion::ALL.iter().copied() {
+            let suites = version.suites();
+            let accepted = self.probe(target, dns_name, addr, version, &suites).await?;
+            let mut scanned = VersionScan {
+                version,
+                accepted,
+                suites: vec![],
+            };
+            if accepted {
+                for suite in suites.iter() {
+                    let offered = std::slice::from_ref(suite);
+                    let accepted = self.probe(target, dns_name, addr, version, offered).await?;
+                    let name = suite.name.clone();
+                    scanned.suites.push(SuiteScan { name, accepted });
+                }
+            }
+            versions.push(scanned);
+        }
+        Ok(versions)
+    }
+
+    /// Whether server completes handshake with version and any of cipher suites,
+    /// errors before handshake are returned
+    async fn probe(
+        &self,
+        target: &Target<'_>,
+        dns_name: webpki::DNSNameRef<'_>,
+        addr: Option<SocketAddr>,
+        version: TlsVersion,
+        suites: &[Suite],
+    ) -> Result<bool, ErrorKind> {
+        let (mut sock, deadline) = self.connect(target, addr).await?;
+        let rustls_version = match version.rustls() {
+            Some(rustls_version) if suites.iter().all(|suite| suite.rustls) => rustls_version,
+            _ => {
+                let ids: Vec<u16> = suites.iter().map(|suite| suite.id).collect();
+                let server_name = Some(target.host).filter(|_| !target.is_ip_address());
+                let probe = scan::probe_hello(&mut sock, version, &ids, server_name);
+                return Ok(matches!(
+                    time::timeout_at(deadline, probe).await,
+                    Ok(Ok(true))
+                ));
+            }
+        };
+
+        let mut config = ClientConfig::clone(&self.config);
+        config.versions = vec![rustls_version];
+        config.ciphersuites = rustls::ALL_CIPHERSUITES
+            .iter()
+            .copied()
+            .filter(|supported| {
+                let id = supported.suite.get_u16();
+                suites.iter().any(|suite| suite.id == id)
+            })
+            .collect();
+        // resumed sessions would skip negotiation
+        config.session_persistence = Arc::new(NoClientSessionStorage {});
+        config.enable_tickets = false;
+        config.enable_sni = !target.is_ip_address();
+        // certificates are not of interest
+        let verifier = Arc::new(RecordingVerifier::new(self.clock_skew, true));
+        config.dangerous().set_certificate_verifier(verifier);
+        let connector = TlsConnector::from(Arc::new(config));
+        let handshake = connector.connect(dns_name, sock);
+        Ok(matches!(
+            time::timeout_at(deadline, handshake).await,
+            Ok(Ok(_))
+        ))
+    }
+
+    /// Scan many targets concurrently
+    ///
+    /// ```
+    /// # use hcc::CheckClient;
+    /// let client = CheckClient::new();
+    /// client.scan_targets(&["sha512.badssl.com", "expired.badssl.com"]);
+    /// ```
+    pub async fn scan_targets<'a>(&'a self, targets: &'a [&str]) -> Vec<ScanResult<'a>> {
+        stream::iter(targets)
+            .map(|target| async move {
+                match self.scan(target).await {
+                    Ok(result) => result,
+                    Err(_) => ScanResult::invalid_target(target),
+                }
+            })
+            .buffered(self.concurrency)
+            .collect()
+            .await
     }
 }
 
-#[derive(Default)]
+/// Protocol version, cipher suite and ALPN protocol negotiated in handshake
+fn negotiated(session: &ClientSession) -> (Option<String>, Option<String>, Option<String>) {
+    let tls_version = session.get_protocol_version().map(|version| match version {
+        ProtocolVersion::TLSv1_2 => "TLSv1.2".to_string(),
+        ProtocolVersion::TLSv1_3 => "TLSv1.3".to_string(),
+        version => format!("{0:?}", version),
+    });
+    let cipher_suite = session
+        .get_negotiated_ciphersuite()
+        .map(|suite| format!("{0:?}", suite.suite));
+    let alpn = session
+        .get_alpn_protocol()
+        .map(|protocol| String::from_utf8_lossy(protocol).to_string());
+    (tls_version, cipher_suite, alpn)
+}
+
+#[derive(Clone, Default)]
 pub struct CheckClientBuilder {
+    all_addresses: bool,
+    alpn_protocols: Vec<String>,
+    attempts: Option<u32>,
+    backoff: Option<Duration>,
+    clock_skew: Duration,
+    concurrency: Option<usize>,
+    connect_timeout: Option<Duration>,
+    critical_in_days: Option<i64>,
+    crypto_policy: Option<CryptoPolicy>,
+    ct_policy: Option<CtPolicy>,
     elapsed: bool,
+    expiry_only: bool,
     grace_in_days: i64,
+    handshake_timeout: Option<Duration>,
+    jitter: Option<Duration>,
+    lifetime_fraction: Option<f64>,
+    pins: HashMap<String, Vec<Pin>>,
+    pkcs12_password: String,
+    query_revocation: bool,
+    resolve: HashMap<String, IpAddr>,
+    roots: Vec<OwnedTrustAnchor>,
+    scan_policy: ScanPolicy,
+    thresholds: HashMap<String, Threshold>,
+    timeout: Option<Duration>,
+    webpki_roots: Option<bool>,
 }
 
 impl CheckClientBuilder {
+    /// Check every resolved address of each target instead of the first reachable one
+    pub fn all_addresses(&mut self, all_addresses: bool) -> &mut Self {
+        self.all_addresses = all_addresses;
+        self
+    }
+
+    /// Protocols offered with ALPN in order of preference, e.g. h2 and http/1.1
+    pub fn alpn_protocols(&mut self, alpn_protocols: &[&str]) -> &mut Self {
+        self.alpn_protocols = alpn_protocols.iter().map(|p| p.to_string()).collect();
+        self
+    }
+
+    /// Maximum number of attempts when check fails with retryable error, at least 1
+    pub fn attempts(&mut self, attempts: u32) -> &mut Self {
+        self.attempts = Some(attempts.max(1));
+        self
+    }
+
+    /// Delay before the first retry, doubled after each retry, 1 second by default
+    pub fn backoff(&mut self, backoff: Duration) -> &mut Self {
+        self.backoff = Some(backoff);
+        self
+    }
+
+    /// Trust certificate authorities in PEM bundle in addition to other roots
+    pub fn ca_file<P: AsRef<Path>>(&mut self, path: P) -> anyhow::Result<&mut Self> {
+        let mut store = RootCertStore::empty();
+        roots::add_pem_file(&mut store, path.as_ref())?;
+        self.roots.extend(store.roots);
+        Ok(self)
+    }
+
+    /// Trust certificate authorities in every PEM file of directory in addition to other roots
+    pub fn ca_dir<P: AsRef<Path>>(&mut self, path: P) -> anyhow::Result<&mut Self> {
+        let mut store = RootCertStore::empty();
+        roots::add_pem_dir(&mut store, path.as_ref())?;
+        self.roots.extend(store.roots);
+        Ok(self)
+    }
+
+    /// Trust certificate authorities of operating system in addition to other roots
+    pub fn native_roots(&mut self) -> anyhow::Result<&mut Self> {
+        let mut store = RootCertStore::empty();
+        roots::add_native(&mut store)?;
+        self.roots.extend(store.roots);
+        Ok(self)
+    }
+
+    /// Trust certificate authorities bundled with webpki-roots, enabled by default
+    pub fn webpki_roots(&mut self, webpki_roots: bool) -> &mut Self {
+        self.webpki_roots = Some(webpki_roots);
+        self
+    }
+
+    /// Tolerance of certificate which is not valid yet because clock of issuer is ahead,
+    /// zero by default
+    pub fn clock_skew(&mut self, clock_skew: Duration) -> &mut Self {
+        self.clock_skew = clock_skew;
+        self
+    }
+
+    /// Maximum number of targets checked at the same time, at least 1
+    pub fn concurrency(&mut self, concurrency: usize) -> &mut Self {
+        self.concurrency = Some(concurrency.max(1));
+        self
+    }
+
+    /// Time allowed to establish TCP connection, 10 seconds by default
+    pub fn connect_timeout(&mut self, connect_timeout: Duration) -> &mut Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Report valid certificates as critical when they expire in this many days or fewer,
+    /// before grace period and lifetime fraction are considered, disabled by default
+    pub fn critical_in_days(&mut self, critical_in_days: i64) -> &mut Self {
+        self.critical_in_days = Some(critical_in_days);
+        self
+    }
+
+    /// Downgrade valid certificates to warning when key or signature in chain is weak by policy,
+    /// disabled by default
+    pub fn crypto_policy(&mut self, crypto_policy: CryptoPolicy) -> &mut Self {
+        self.crypto_policy = Some(crypto_policy);
+        self
+    }
+
+    /// Verify signed certificate timestamps with logs in log list, and downgrade valid
+    /// certificates to warning when too few logs signed them, disabled by default
+    pub fn ct_policy(&mut self, ct_policy: CtPolicy) -> &mut Self {
+        self.ct_policy = Some(ct_policy);
+        self
+    }
+
     pub fn elapsed(&mut self, elapsed: bool) -> &mut Self {
         self.elapsed = elapsed;
         self
     }
 
+    /// Complete handshake with certificates which are not trusted and report their dates,
+    /// results are marked as untrusted instead of invalid
+    pub fn expiry_only(&mut self, expiry_only: bool) -> &mut Self {
+        self.expiry_only = expiry_only;
+        self
+    }
+
+    /// Report valid certificates as warning when they expire in this many days or fewer
     pub fn grace_in_days(&mut self, grace_in_days: i64) -> &mut Self {
         self.grace_in_days = grace_in_days;
         self
     }
 
+    /// Time allowed for STARTTLS dialog and TLS handshake, 10 seconds by default
+    pub fn handshake_timeout(&mut self, handshake_timeout: Duration) -> &mut Self {
+        self.handshake_timeout = Some(handshake_timeout);
+        self
+    }
+
+    /// Maximum random delay added to each backoff, 500 milliseconds by default
+    pub fn jitter(&mut self, jitter: Duration) -> &mut Self {
+        self.jitter = Some(jitter);
+        self
+    }
+
+    /// Warn when this fraction of lifetime of certificate has elapsed, e.g. 2/3 like certbot,
+    /// in addition to grace period, disabled by default
+    pub fn lifetime_fraction(&mut self, lifetime_fraction: f64) -> &mut Self {
+        self.lifetime_fraction = Some(lifetime_fraction);
+        self
+    }
+
+    /// Expect certificate or public key in chain served for target to match any of its pins,
+    /// otherwise the result is pin mismatch. Target is `host:port`, or `host` for every port
+    /// without pins of its own
+    pub fn pin(&mut self, target: &str, pin: Pin) -> &mut Self {
+        self.pins.entry(target.to_string()).or_default().push(pin);
+        self
+    }
+
+    /// Password to decrypt PKCS#12 archives, empty by default
+    pub fn pkcs12_password(&mut self, pkcs12_password: &str) -> &mut Self {
+        self.pkcs12_password = pkcs12_password.to_string();
+        self
+    }
+
+    /// Query OCSP responders and CRL distribution points of certificate when server does not
+    /// staple OCSP response, each within handshake timeout, disabled by default
+    pub fn query_revocation(&mut self, query_revocation: bool) -> &mut Self {
+        self.query_revocation = query_revocation;
+        self
+    }
+
+    /// Connect to the address instead of resolving domain name, like `curl --resolve`,
+    /// while the domain name is still sent as SNI and verified against certificate
+    pub fn resolve(&mut self, domain_name: &str, address: IpAddr) -> &mut Self {
+        self.resolve.insert(domain_name.to_string(), address);
+        self
+    }
+
+    /// Protocol versions and cipher suites allowed by scan, TLS 1.2+ without CBC suites by default
+    pub fn scan_policy(&mut self, scan_policy: ScanPolicy) -> &mut Self {
+        self.scan_policy = scan_policy;
+        self
+    }
+
+    /// Warn by threshold for target instead of grace period and lifetime fraction.
+    /// Target is `host:port`, or `host` for every port without threshold of its own
+    pub fn threshold(&mut self, target: &str, threshold: Threshold) -> &mut Self {
+        self.thresholds.insert(target.to_string(), threshold);
+        self
+    }
+
+    /// Time allowed for each attempt from DNS lookup to the end of handshake,
+    /// 30 seconds by default
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
     pub fn build(&self) -> CheckClient {
+        let mut config = ClientConfig::new();
+        if self.webpki_roots.unwrap_or(true) {
+            config
+                .root_store
+                .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+        }
+        config.root_store.roots.extend(self.roots.iter().cloned());
+        config.alpn_protocols = self
+            .alpn_protocols
+            .iter()
+            .map(|protocol| protocol.as_bytes().to_vec())
+            .collect();
         CheckClient {
+            all_addresses: self.all_addresses,
+            attempts: self.attempts.unwrap_or(1),
+            backoff: self.backoff.unwrap_or(DEFAULT_BACKOFF),
+            clock_skew: self.clock_skew,
+            concurrency: self.concurrency.unwrap_or(DEFAULT_CONCURRENCY),
+            config: Arc::new(config),
+            connect_timeout: self.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT),
+            critical_in_days: self.critical_in_days,
+            crypto_policy: self.crypto_policy.clone(),
+            ct_policy: self.ct_policy.clone(),
             elapsed: self.elapsed,
+            expiry_only: self.expiry_only,
             grace_in_days: self.grace_in_days,
+            handshake_timeout: self.handshake_timeout.unwrap_or(DEFAULT_HANDSHAKE_TIMEOUT),
+            jitter: self.jitter.unwrap_or(DEFAULT_JITTER),
+            lifetime_fraction: self.lifetime_fraction,
+            pins: self.pins.clone(),
+            pkcs12_password: self.pkcs12_password.clone(),
+            query_revocation: self.query_revocation,
+            resolve: self.resolve.clone(),
+            scan_policy: self.scan_policy.clone(),
+            thresholds: self.thresholds.clone(),
+            timeout: self.timeout.unwrap_or(DEFAULT_TIMEOUT),
             ..Default::default()
         }
     }
@@ -183,10 +1162,37 @@ impl CheckClientBuilder {
 
 #[cfg(test)]
 mod test {
-    use chrono::{TimeZone, Utc};
+    use std::fs;
+
+    use std::net::TcpListener;
+    use std::time::{Duration as StdDuration, Instant};
 
+    use chrono::{Duration, TimeZone, Utc};
+
+    use crate::certificate::{CertificateInfo, CryptoPolicy};
     use crate::check_client::CheckClient;
-    use crate::check_result::CheckState;
+    use rustls::{Certificate, ProtocolVersion};
+
+    use crate::check_result::{CheckResult, CheckState, ErrorKind};
+    use crate::ct::{encode_list, CtLogList, CtPolicy, SctSource};
+    use crate::fixtures::{
+        builder_for, client_for, http_listener, serve_http, temp_dir, Step, TestLog, TestServer,
+    };
+    use crate::pin::Pin;
+    use crate::revocation::RevocationStatus;
+    use crate::scan::{ScanPolicy, TlsVersion};
+
+    async fn check_fake_server(scheme: &str, script: &'static [Step]) -> CheckState {
+        let server = TestServer::new();
+        let port = server.serve(script);
+        let client = client_for(&server);
+        let target = format!("{0}://localhost:{1}", scheme, port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(scheme, result.protocol.to_string());
+        assert_eq!(port, result.port);
+        assert!(result.days >= 89);
+        result.state
+    }
 
     #[tokio::test]
     async fn test_good_certificate() {
@@ -206,23 +1212,105 @@ mod test {
         let result = client.check_certificate(domain_name).await.unwrap();
         assert!(matches!(result.state, CheckState::Expired));
         assert!(result.checked_at > 0);
-        assert_eq!(0, result.not_after);
+        assert!(result.not_after > 0);
+        assert!(result.not_after < result.checked_at);
     }
 
     #[tokio::test]
     async fn test_check_certificates() {
         let domain_names = vec!["sha512.badssl.com", "expired.badssl.com"];
         let client = CheckClient::new();
-        let results = client.check_certificates(domain_names.as_slice()).unwrap();
+        let results = client.check_certificates(domain_names.as_slice()).await;
         assert_eq!(2, results.len());
 
-        let result = results.get(0).unwrap();
+        let result = results.first().unwrap();
         assert!(matches!(result.state, CheckState::Ok));
 
         let result = results.get(1).unwrap();
         assert!(matches!(result.state, CheckState::Expired));
     }
 
+    #[tokio::test]
+    async fn test_check_certificates_isolate_errors() {
+        let server = TestServer::new();
+        let port = server.serve(&[]);
+        let client = client_for(&server);
+        let target = format!("localhost:{0}", port);
+        let targets = vec![
+            "nonexistent.invalid",
+            target.as_str(),
+            "gopher://example.com",
+            "example..com",
+        ];
+        let results = client.check_certificates(&targets).await;
+        assert_eq!(4, results.len());
+        assert_eq!(CheckState::Error(ErrorKind::Dns), results[0].state);
+        assert_eq!(CheckState::Ok, results[1].state);
+        assert_eq!(port, results[1].port);
+        assert_eq!(
+            CheckState::Error(ErrorKind::InvalidTarget),
+            results[2].state
+        );
+        assert_eq!("gopher://example.com", results[2].domain_name);
+        assert_eq!(
+            CheckState::Error(ErrorKind::InvalidTarget),
+            results[3].state
+        );
+        assert_eq!("example..com", results[3].domain_name);
+    }
+
+    async fn check_slow_servers(concurrency: usize) -> u128 {
+        let server = TestServer::new();
+        let targets: Vec<String> = (0..3)
+            .map(|_| format!("localhost:{0}", server.serve(&[Step::Sleep(500)])))
+            .collect();
+        let targets: Vec<&str> = targets.iter().map(AsRef::as_ref).collect();
+        let client = builder_for(&server).concurrency(concurrency).build();
+        let origin = Instant::now();
+        let results = client.check_certificates(&targets).await;
+        let elapsed = origin.elapsed().as_millis();
+        assert_eq!(3, results.len());
+        for result in results {
+            assert_eq!(CheckState::Ok, result.state);
+        }
+        elapsed
+    }
+
+    async fn check_silent_server(server: &TestServer, client: &CheckClient) -> CheckState {
+        let port = server.serve(&[Step::Sleep(2000)]);
+        let target = format!("localhost:{0}", port);
+        let origin = Instant::now();
+        let result = client.check_certificate(&target).await.unwrap();
+        assert!(origin.elapsed().as_millis() < 1000);
+        result.state
+    }
+
+    #[tokio::test]
+    async fn test_check_certificate_handshake_timeout() {
+        let server = TestServer::new();
+        let client = builder_for(&server)
+            .handshake_timeout(StdDuration::from_millis(200))
+            .build();
+        let state = check_silent_server(&server, &client).await;
+        assert_eq!(CheckState::Error(ErrorKind::Timeout), state);
+    }
+
+    #[tokio::test]
+    async fn test_check_certificate_timeout() {
+        let server = TestServer::new();
+        let client = builder_for(&server)
+            .timeout(StdDuration::from_millis(200))
+            .build();
+        let state = check_silent_server(&server, &client).await;
+        assert_eq!(CheckState::Error(ErrorKind::Timeout), state);
+    }
+
+    #[tokio::test]
+    async fn test_check_certificates_concurrently() {
+        assert!(check_slow_servers(3).await < 1200);
+        assert!(check_slow_servers(1).await >= 1500);
+    }
+
     #[tokio::test]
     async fn test_check_certificate_with_grace_in_days() {
         let domain_name = "sha512.badssl.com";
@@ -237,4 +1325,1028 @@ mod test {
         let result = client.check_certificate(domain_name).await.unwrap();
         assert!(matches!(result.state, CheckState::Warning));
     }
+
+    #[tokio::test]
+    async fn test_check_certificate_with_port() {
+        let state = check_fake_server("https", &[]).await;
+        assert!(matches!(state, CheckState::Ok));
+    }
+
+    #[tokio::test]
+    async fn test_starttls_smtp() {
+        let script = &[
+            Step::Send("220-localhost ESMTP\r\n220 ready\r\n"),
+            Step::Expect("EHLO localhost\r\n"),
+            Step::Send("250-localhost\r\n250-PIPELINING\r\n250 STARTTLS\r\n"),
+            Step::Expect("STARTTLS\r\n"),
+            Step::Send("220 2.0.0 Ready to start TLS\r\n"),
+        ];
+        let state = check_fake_server("smtp", script).await;
+        assert!(matches!(state, CheckState::Ok));
+    }
+
+    #[tokio::test]
+    async fn test_starttls_imap() {
+        let script = &[
+            Step::Send("* OK [CAPABILITY IMAP4rev1 STARTTLS] ready\r\n"),
+            Step::Expect("a001 STARTTLS\r\n"),
+            Step::Send("a001 OK Begin TLS negotiation now\r\n"),
+        ];
+        let state = check_fake_server("imap", script).await;
+        assert!(matches!(state, CheckState::Ok));
+    }
+
+    #[tokio::test]
+    async fn test_starttls_pop3() {
+        let script = &[
+            Step::Send("+OK POP3 ready\r\n"),
+            Step::Expect("STLS\r\n"),
+            Step::Send("+OK Begin TLS negotiation\r\n"),
+        ];
+        let state = check_fake_server("pop3", script).await;
+        assert!(matches!(state, CheckState::Ok));
+    }
+
+    #[tokio::test]
+    async fn test_starttls_ftp() {
+        let script = &[
+            Step::Send("220 FTP server ready\r\n"),
+            Step::Expect("AUTH TLS\r\n"),
+            Step::Send("234 AUTH TLS successful\r\n"),
+        ];
+        let state = check_fake_server("ftp", script).await;
+        assert!(matches!(state, CheckState::Ok));
+    }
+
+    #[tokio::test]
+    async fn test_starttls_xmpp() {
+        let script = &[
+            Step::Expect(concat!(
+                "<?xml version='1.0'?>",
+                "<stream:stream to='localhost' version='1.0' xmlns='jabber:client' ",
+                "xmlns:stream='http://etherx.jabber.org/streams'>"
+            )),
+            Step::Send(concat!(
+                "<?xml version='1.0'?>",
+                "<stream:stream from='localhost' id='1' version='1.0' xmlns='jabber:client' ",
+                "xmlns:stream='http://etherx.jabber.org/streams'>",
+                "<stream:features>",
+                "<starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'><required/></starttls>",
+                "</stream:features>"
+            )),
+            Step::Expect("<starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>"),
+            Step::Send("<proceed xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>"),
+        ];
+        let state = check_fake_server("xmpp", script).await;
+        assert!(matches!(state, CheckState::Ok));
+    }
+
+    #[tokio::test]
+    async fn test_starttls_postgres() {
+        let script = &[
+            Step::ExpectBytes(&[0, 0, 0, 8, 0x04, 0xd2, 0x16, 0x2f]),
+            Step::SendBytes(b"S"),
+        ];
+        let state = check_fake_server("postgres", script).await;
+        assert!(matches!(state, CheckState::Ok));
+    }
+
+    #[tokio::test]
+    async fn test_starttls_mysql() {
+        let script = &[
+            Step::SendBytes(&[
+                // header
+                34, 0, 0, 0, //
+                // protocol version and server version
+                10, b'8', b'.', b'0', b'.', b'0', 0, //
+                // connection ID
+                1, 0, 0, 0, //
+                // auth-plugin-data-part-1 and filler
+                b'a', b'b', b'c', b'd', b'e', b'f', b'g', b'h', 0, //
+                // capability flags, character set and status flags
+                0x00, 0x8a, 33, 2, 0, //
+                // capability flags (upper 2 bytes), auth data length and reserved
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            ]),
+            Step::ExpectBytes(&[
+                32, 0, 0, 1, //
+                0x00, 0x8a, 0, 0, //
+                0, 0, 0, 1,  //
+                33, //
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            ]),
+        ];
+        let state = check_fake_server("mysql", script).await;
+        assert!(matches!(state, CheckState::Ok));
+    }
+
+    #[tokio::test]
+    async fn test_starttls_ldap() {
+        let script = &[
+            Step::ExpectBytes(b"\x30\x1d\x02\x01\x01\x77\x18\x80\x161.3.6.1.4.1.1466.20037"),
+            Step::SendBytes(&[
+                0x30, 0x0c, 0x02, 0x01, 0x01, 0x78, 0x07, 0x0a, 0x01, 0x00, 0x04, 0x00, 0x04, 0x00,
+            ]),
+        ];
+        let state = check_fake_server("ldap", script).await;
+        assert!(matches!(state, CheckState::Ok));
+    }
+
+    #[tokio::test]
+    async fn test_starttls_refused() {
+        let server = TestServer::new();
+        let port = server.serve(&[
+            Step::Send("220 localhost ESMTP\r\n"),
+            Step::Expect("EHLO localhost\r\n"),
+            Step::Send("250 localhost\r\n"),
+            Step::Expect("STARTTLS\r\n"),
+            Step::Send("454 TLS not available\r\n"),
+        ]);
+        let client = client_for(&server);
+        let target = format!("smtp://localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Error(ErrorKind::Starttls), result.state);
+    }
+
+    #[tokio::test]
+    async fn test_untrusted_issuer() {
+        let server = TestServer::new();
+        let port = server.serve(&[]);
+        let client = CheckClient::new();
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(
+            CheckState::Invalid(ErrorKind::UntrustedIssuer),
+            result.state
+        );
+        assert!(result.not_after > result.checked_at);
+        assert!(result.days >= 89);
+    }
+
+    #[tokio::test]
+    async fn test_expired() {
+        let now = Utc::now();
+        let server = TestServer::with_validity(now - Duration::days(100), now - Duration::days(10));
+        let port = server.serve(&[]);
+        let client = client_for(&server);
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Expired, result.state);
+        assert!(result.not_after < result.checked_at);
+        assert!(result.days <= -9);
+    }
+
+    #[tokio::test]
+    async fn test_not_yet_valid() {
+        let now = Utc::now();
+        let server = TestServer::with_validity(now + Duration::days(10), now + Duration::days(100));
+        let port = server.serve(&[]);
+        let client = client_for(&server);
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::NotYetValid, result.state);
+        assert!(result.not_after > result.checked_at);
+        assert!(result.not_before > result.checked_at);
+    }
+
+    #[tokio::test]
+    async fn test_not_yet_valid_within_clock_skew() {
+        let now = Utc::now();
+        let server =
+            TestServer::with_validity(now + Duration::minutes(3), now + Duration::days(100));
+        let port = server.serve(&[]);
+        let client = builder_for(&server)
+            .clock_skew(StdDuration::from_secs(300))
+            .build();
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+        assert!(result.not_before > result.checked_at);
+    }
+
+    #[tokio::test]
+    async fn test_name_mismatch() {
+        let server = TestServer::with_name("example.com");
+        let port = server.serve(&[]);
+        let client = client_for(&server);
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Invalid(ErrorKind::NameMismatch), result.state);
+        assert!(result.not_after > result.checked_at);
+    }
+
+    #[tokio::test]
+    async fn test_handshake_alert() {
+        let server = TestServer::new();
+        // fatal handshake_failure alert
+        let port = server.serve(&[Step::SendBytes(&[0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 0x28])]);
+        let client = CheckClient::new();
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Error(ErrorKind::HandshakeAlert), result.state);
+        assert_eq!(0, result.not_after);
+    }
+
+    #[tokio::test]
+    async fn test_connection_refused() {
+        let port = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().port()
+        };
+        let client = CheckClient::new();
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(
+            CheckState::Error(ErrorKind::ConnectionRefused),
+            result.state
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_connection_refused() {
+        let port = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().port()
+        };
+        let client = CheckClient::builder()
+            .attempts(3)
+            .backoff(StdDuration::from_millis(100))
+            .jitter(StdDuration::ZERO)
+            .build();
+        let target = format!("localhost:{0}", port);
+        let origin = Instant::now();
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(
+            CheckState::Error(ErrorKind::ConnectionRefused),
+            result.state
+        );
+        assert_eq!(3, result.attempts);
+        // 100ms before 2nd attempt and 200ms before 3rd attempt
+        assert!(origin.elapsed().as_millis() >= 300);
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_invalid_certificate() {
+        let server = TestServer::with_name("example.com");
+        let port = server.serve(&[]);
+        let client = builder_for(&server).attempts(3).build();
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Invalid(ErrorKind::NameMismatch), result.state);
+        assert_eq!(1, result.attempts);
+        assert_eq!(Some(false), result.name_matched);
+        assert_eq!(vec!["example.com".to_string()], result.subject_alt_names());
+        assert_eq!(Some("example.com"), result.common_name());
+    }
+
+    #[tokio::test]
+    async fn test_check_addresses() {
+        let server = TestServer::new();
+        let port = server.serve(&[]);
+        let client = client_for(&server);
+        let target = format!("localhost:{0}", port);
+        let results = client.check_addresses(&target).await.unwrap();
+        let localhost = "127.0.0.1".parse().unwrap();
+        let result = results
+            .iter()
+            .find(|result| result.address == Some(localhost))
+            .unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+        assert_eq!("localhost", result.domain_name);
+        assert!(!result.inconsistent);
+    }
+
+    #[tokio::test]
+    async fn test_addresses_serve_different_certificates() {
+        let first = TestServer::new();
+        let second = TestServer::new();
+        // session ID of TLS 1.2 is cached by client without reading after handshake
+        let mut first_config = first.server_config();
+        first_config.versions = vec![ProtocolVersion::TLSv1_2];
+        let mut second_config = second.server_config();
+        second_config.versions = vec![ProtocolVersion::TLSv1_2];
+        // addresses behind load balancer share sessions
+        second_config.session_storage = first_config.session_storage.clone();
+        let port = first.serve_forever_at(first_config, "127.0.0.1:0");
+        second.serve_forever_at(second_config, &format!("127.0.0.2:{0}", port));
+
+        let dir = temp_dir("shared_sessions");
+        let ca_file = dir.join("ca.pem");
+        fs::write(&ca_file, second.ca_pem()).unwrap();
+        let client = builder_for(&first).ca_file(&ca_file).unwrap().build();
+        let target = format!("localhost:{0}@127.0.0.1", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+        let first_leaf = result.chain[0].fingerprint.clone();
+
+        let target = format!("localhost:{0}@127.0.0.2", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+        assert_ne!(first_leaf, result.chain[0].fingerprint);
+        let leaf = CertificateInfo::parse(&second.chain()[0].0, &Utc::now()).unwrap();
+        assert_eq!(leaf.fingerprint, result.chain[0].fingerprint);
+    }
+
+    #[test]
+    fn test_mark_inconsistent() {
+        let leaf = |b: u8| Some(Certificate(vec![b]));
+
+        let mut checked = vec![
+            (CheckResult::default(), leaf(1)),
+            (CheckResult::default(), leaf(1)),
+            (CheckResult::default(), None),
+        ];
+        CheckClient::mark_inconsistent(&mut checked);
+        assert!(checked.iter().all(|(result, _)| !result.inconsistent));
+
+        checked[1].1 = leaf(2);
+        CheckClient::mark_inconsistent(&mut checked);
+        assert!(checked[0].0.inconsistent);
+        assert!(checked[1].0.inconsistent);
+        assert!(!checked[2].0.inconsistent);
+    }
+
+    #[tokio::test]
+    async fn test_check_certificate_with_address() {
+        let server = TestServer::with_name("example.com");
+        let port = server.serve(&[]);
+        let client = client_for(&server);
+        let target = format!("example.com:{0}@127.0.0.1", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+        assert_eq!("example.com", result.domain_name);
+        assert_eq!(Some("127.0.0.1".parse().unwrap()), result.address);
+    }
+
+    #[tokio::test]
+    async fn test_check_certificate_with_resolve() {
+        let server = TestServer::with_name("example.com");
+        let port = server.serve(&[]);
+        let client = builder_for(&server)
+            .resolve("example.com", "127.0.0.1".parse().unwrap())
+            .build();
+        let target = format!("example.com:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+        assert_eq!(Some("127.0.0.1".parse().unwrap()), result.address);
+    }
+
+    #[tokio::test]
+    async fn test_intermediate_expires_first() {
+        let now = Utc::now();
+        let server =
+            TestServer::with_intermediate(now - Duration::days(1), now + Duration::days(10));
+        let port = server.serve(&[]);
+        let client = builder_for(&server).grace_in_days(30).build();
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Warning, result.state);
+        assert_eq!(2, result.chain.len());
+        assert!(result.chain[0].days >= 89);
+        assert_eq!(result.chain[1].days, result.days);
+        assert_eq!(result.chain[1].not_after, result.not_after);
+        assert!(result.days <= 10);
+    }
+
+    #[tokio::test]
+    async fn test_lifetime_fraction() {
+        let now = Utc::now();
+        // 60 of 90 days elapsed, expires in 30 days
+        let server =
+            TestServer::with_intermediate(now - Duration::days(60), now + Duration::days(30));
+        let port = server.serve_forever(server.server_config());
+        let target = format!("localhost:{0}", port);
+        let client = builder_for(&server)
+            .grace_in_days(7)
+            .lifetime_fraction(2.0 / 3.0)
+            .build();
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Warning, result.state);
+
+        let client = builder_for(&server)
+            .grace_in_days(7)
+            .lifetime_fraction(0.75)
+            .build();
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+
+        // threshold of domain name replaces grace period and lifetime fraction
+        let threshold = "14d,0.75".parse().unwrap();
+        let client = builder_for(&server)
+            .lifetime_fraction(0.5)
+            .threshold("localhost", threshold)
+            .build();
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+
+        // threshold of host and port takes precedence over threshold of host,
+        // and thresholds of other ports are ignored
+        let client = builder_for(&server)
+            .threshold("localhost", threshold)
+            .threshold(&target, "30d".parse().unwrap())
+            .threshold(&format!("localhost:{0}", port + 1), "7d".parse().unwrap())
+            .build();
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Warning, result.state);
+    }
+
+    #[tokio::test]
+    async fn test_critical_in_days() {
+        let now = Utc::now();
+        let server =
+            TestServer::with_intermediate(now - Duration::days(60), now + Duration::days(10));
+        let port = server.serve_forever(server.server_config());
+        let target = format!("localhost:{0}", port);
+        let client = builder_for(&server)
+            .grace_in_days(30)
+            .critical_in_days(7)
+            .build();
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Warning, result.state);
+
+        let client = builder_for(&server)
+            .grace_in_days(30)
+            .critical_in_days(14)
+            .build();
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Critical, result.state);
+    }
+
+    #[tokio::test]
+    async fn test_expired_intermediate() {
+        let now = Utc::now();
+        let server =
+            TestServer::with_intermediate(now - Duration::days(30), now - Duration::days(1));
+        let port = server.serve(&[]);
+        let client = client_for(&server);
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Expired, result.state);
+        assert_eq!(2, result.chain.len());
+        assert!(result.days < 0);
+    }
+
+    #[tokio::test]
+    async fn test_ca_file() {
+        let server = TestServer::new();
+        let port = server.serve(&[]);
+        let dir = temp_dir("ca_file");
+        let path = dir.join("ca.pem");
+        fs::write(&path, server.ca_pem()).unwrap();
+        let client = CheckClient::builder()
+            .webpki_roots(false)
+            .ca_file(&path)
+            .unwrap()
+            .build();
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+    }
+
+    #[tokio::test]
+    async fn test_ca_dir() {
+        let server = TestServer::new();
+        let port = server.serve(&[]);
+        let dir = temp_dir("ca_dir");
+        fs::write(dir.join("other.pem"), TestServer::new().ca_pem()).unwrap();
+        fs::write(dir.join("ca.pem"), server.ca_pem()).unwrap();
+        let client = CheckClient::builder().ca_dir(&dir).unwrap().build();
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+    }
+
+    #[test]
+    fn test_ca_file_invalid() {
+        let dir = temp_dir("ca_file_invalid");
+        fs::write(dir.join("ca.pem"), "not a certificate").unwrap();
+        assert!(CheckClient::builder().ca_file(dir.join("ca.pem")).is_err());
+        assert!(CheckClient::builder().ca_dir(&dir).is_err());
+    }
+
+    async fn check_expiry_only(server: TestServer) -> (CheckState, Option<ErrorKind>, i64) {
+        let port = server.serve(&[]);
+        let client = CheckClient::builder().expiry_only(true).build();
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        (result.state, result.untrusted, result.days)
+    }
+
+    #[tokio::test]
+    async fn test_expiry_only_untrusted() {
+        let (state, untrusted, days) = check_expiry_only(TestServer::new()).await;
+        assert_eq!(CheckState::Ok, state);
+        assert_eq!(Some(ErrorKind::UntrustedIssuer), untrusted);
+        assert!(days >= 89);
+    }
+
+    #[tokio::test]
+    async fn test_expiry_only_expired() {
+        let now = Utc::now();
+        let server = TestServer::with_validity(now - Duration::days(30), now - Duration::days(1));
+        let (state, untrusted, days) = check_expiry_only(server).await;
+        assert_eq!(CheckState::Expired, state);
+        assert_eq!(Some(ErrorKind::UntrustedIssuer), untrusted);
+        assert!(days < 0);
+    }
+
+    #[tokio::test]
+    async fn test_expiry_only_not_yet_valid() {
+        let now = Utc::now();
+        let server = TestServer::with_validity(now + Duration::days(1), now + Duration::days(30));
+        let (state, untrusted, _) = check_expiry_only(server).await;
+        assert_eq!(CheckState::NotYetValid, state);
+        assert_eq!(Some(ErrorKind::UntrustedIssuer), untrusted);
+    }
+
+    #[tokio::test]
+    async fn test_expiry_only_trusted() {
+        let now = Utc::now();
+        let server = TestServer::with_validity(now - Duration::days(30), now - Duration::days(1));
+        let port = server.serve(&[]);
+        let client = builder_for(&server).expiry_only(true).build();
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Expired, result.state);
+        assert_eq!(None, result.untrusted);
+    }
+
+    #[tokio::test]
+    async fn test_ip_address_host() {
+        // certificate of localhost cannot be verified against IP address,
+        // so only expiry is checked and trust is recorded regardless of name
+        let server = TestServer::new();
+        let port = server.serve_forever(server.server_config());
+        let client = client_for(&server);
+        let target = format!("127.0.0.1:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+        assert_eq!("127.0.0.1", result.domain_name);
+        assert_eq!(port, result.port);
+        assert_eq!(None, result.untrusted);
+        assert_eq!(Some(false), result.name_matched);
+
+        let client = CheckClient::default();
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+        assert_eq!(Some(ErrorKind::UntrustedIssuer), result.untrusted);
+
+        let now = Utc::now();
+        let server = TestServer::with_validity(now - Duration::days(30), now - Duration::days(1));
+        let port = server.serve_forever(server.server_config());
+        let target = format!("127.0.0.1:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Expired, result.state);
+    }
+
+    #[tokio::test]
+    async fn test_dns_error() {
+        let client = CheckClient::new();
+        let result = client
+            .check_certificate("nonexistent.invalid")
+            .await
+            .unwrap();
+        assert_eq!(CheckState::Error(ErrorKind::Dns), result.state);
+    }
+
+    #[test]
+    fn test_check_file() {
+        let now = Utc::now();
+        let server =
+            TestServer::with_intermediate(now - Duration::days(30), now + Duration::days(5));
+        let dir = temp_dir("check_file");
+        let pem = dir.join("fullchain.pem").display().to_string();
+        fs::write(&pem, server.chain_pem()).unwrap();
+        let p12 = dir.join("bundle.p12").display().to_string();
+        fs::write(&p12, server.pkcs12("secret")).unwrap();
+        let missing = dir.join("missing.pem").display().to_string();
+
+        let client = CheckClient::builder()
+            .grace_in_days(7)
+            .pkcs12_password("secret")
+            .build();
+        let paths = [pem.as_str(), p12.as_str(), missing.as_str()];
+        let results = client.check_files(&paths);
+        for result in &results[..2] {
+            assert_eq!(CheckState::Warning, result.state);
+            assert_eq!("file", result.protocol.to_string());
+            assert_eq!(2, result.chain.len());
+            assert!(result.days <= 5);
+        }
+        assert_eq!(pem, results[0].target());
+        let error = CheckState::Error(ErrorKind::File);
+        assert_eq!(error, results[2].state);
+
+        let client = CheckClient::new();
+        let result = client.check_file(&p12);
+        assert_eq!(CheckState::Error(ErrorKind::Password), result.state);
+    }
+
+    #[tokio::test]
+    async fn test_verify_pair() {
+        let server = TestServer::new();
+        let dir = temp_dir("check_client_verify_pair");
+        let certificate = dir.join("cert.pem");
+        fs::write(&certificate, server.chain_pem()).unwrap();
+        let key = dir.join("key.pem");
+        fs::write(&key, server.key_pem()).unwrap();
+        let client = client_for(&server);
+
+        let result = client
+            .verify_pair(&certificate, &key, "", None)
+            .await
+            .unwrap();
+        assert!(result.matched);
+        assert!(result.served.is_none());
+
+        let target = format!("localhost:{0}", server.serve(&[]));
+        let result = client
+            .verify_pair(&certificate, &key, "", Some(&target))
+            .await
+            .unwrap();
+        assert_eq!(Some(true), result.served_matched);
+        assert!(result.is_ok());
+
+        let other = TestServer::new();
+        let target = format!("localhost:{0}", other.serve(&[]));
+        let result = client
+            .verify_pair(&certificate, &key, "", Some(&target))
+            .await
+            .unwrap();
+        assert_eq!(Some(false), result.served_matched);
+        assert!(!result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_crypto_policy() {
+        let server = TestServer::with_rsa_key();
+        let port = server.serve(&[]);
+        let policy = CryptoPolicy {
+            min_rsa_bits: 3072,
+            ..Default::default()
+        };
+        let client = builder_for(&server).crypto_policy(policy).build();
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Warning, result.state);
+        assert_eq!(vec!["RSA-2048 key".to_string()], result.weak_crypto);
+        assert_eq!("RSA-2048", result.chain[0].key_algorithm);
+        assert_eq!("ecdsa-with-SHA256", result.chain[0].signature_algorithm);
+
+        let port = server.serve(&[]);
+        let client = builder_for(&server)
+            .crypto_policy(CryptoPolicy::default())
+            .build();
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+        assert!(result.weak_crypto.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_negotiated() {
+        let server = TestServer::new();
+        let dir = temp_dir("negotiated");
+        let ca_file = dir.join("ca.pem");
+        fs::write(&ca_file, server.ca_pem()).unwrap();
+        let client = CheckClient::builder()
+            .ca_file(&ca_file)
+            .unwrap()
+            .alpn_protocols(&["h2", "http/1.1"])
+            .build();
+
+        let port = server.serve_alpn(&[], &["h2"]);
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+        assert_eq!(Some("TLSv1.3".to_string()), result.tls_version);
+        assert!(result.cipher_suite.unwrap().starts_with("TLS13_"));
+        assert_eq!(Some("h2".to_string()), result.alpn);
+
+        // server without HTTP/2 falls back to HTTP/1.1
+        let port = server.serve_alpn(&[], &["http/1.1"]);
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(Some("http/1.1".to_string()), result.alpn);
+
+        let port = server.serve(&[]);
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(None, result.alpn);
+    }
+
+    #[tokio::test]
+    async fn test_scan() {
+        let server = TestServer::new();
+        let mut config = server.server_config();
+        config.versions = vec![ProtocolVersion::TLSv1_2];
+        let port = server.serve_forever(config);
+        let target = format!("localhost:{0}", port);
+
+        let client = CheckClient::new();
+        let result = client.scan(&target).await.unwrap();
+        assert_eq!(None, result.error);
+        let accepted: Vec<(String, bool)> = result
+            .versions
+            .iter()
+            .map(|scanned| (scanned.version.to_string(), scanned.accepted))
+            .collect();
+        let expected = vec![
+            ("TLSv1.3".to_string(), false),
+            ("TLSv1.2".to_string(), true),
+            ("TLSv1.1".to_string(), false),
+            ("TLSv1.0".to_string(), false),
+        ];
+        assert_eq!(expected, accepted);
+        // leaf certificate has ECDSA key and CBC suites are not implemented by server
+        let accepted: Vec<&str> = result.versions[1]
+            .suites
+            .iter()
+            .filter(|suite| suite.accepted)
+            .map(|suite| suite.name.as_str())
+            .collect();
+        let expected = vec![
+            "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256",
+            "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384",
+            "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256",
+        ];
+        assert_eq!(expected, accepted);
+        assert!(result.versions[0].suites.is_empty());
+        assert!(result.is_compliant());
+
+        let client = CheckClient::builder()
+            .scan_policy(ScanPolicy {
+                min_version: TlsVersion::Tls13,
+                allow_cbc: false,
+            })
+            .build();
+        let result = client.scan(&target).await.unwrap();
+        assert_eq!(vec!["TLSv1.2 accepted"], result.violations);
+        assert!(!result.is_compliant());
+    }
+
+    #[tokio::test]
+    async fn test_scan_targets_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let client = CheckClient::new();
+        let refused = format!("localhost:{0}", port);
+        let targets = [refused.as_str(), "example.com:-1"];
+        let results = client.scan_targets(&targets).await;
+        assert_eq!(Some(ErrorKind::ConnectionRefused), results[0].error);
+        assert!(results[0].versions.is_empty());
+        assert_eq!(Some(ErrorKind::InvalidTarget), results[1].error);
+        assert_eq!(
+            "[!] failed to scan example.com:-1: invalid target",
+            results[1].to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stapled_revocation() {
+        let (_, url) = http_listener();
+        let server = TestServer::with_revocation_url(&url);
+        let client = client_for(&server);
+        let next_update = Utc::now() + Duration::days(1);
+
+        let mut expected = vec![
+            (RevocationStatus::Good, CheckState::Ok),
+            (
+                RevocationStatus::Revoked,
+                CheckState::Invalid(ErrorKind::Revoked),
+            ),
+            (RevocationStatus::Unknown, CheckState::Ok),
+        ];
+        for (status, state) in expected.drain(..) {
+            let mut config = server.server_config();
+            let ocsp = server.ocsp_response(status, next_update);
+            config
+                .set_single_cert_with_ocsp_and_sct(
+                    server.chain().to_vec(),
+                    server.key().clone(),
+                    ocsp,
+                    vec![],
+                )
+                .unwrap();
+            let port = server.serve_forever(config);
+            let target = format!("localhost:{0}", port);
+            let result = client.check_certificate(&target).await.unwrap();
+            assert_eq!(state, result.state);
+            assert_eq!(Some(status), result.revocation);
+        }
+
+        // revocation is not reported without staple unless querying is enabled
+        let port = server.serve_forever(server.server_config());
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+        assert_eq!(None, result.revocation);
+    }
+
+    #[tokio::test]
+    async fn test_forged_issuer_revocation() {
+        let (listener, url) = http_listener();
+        let server = TestServer::with_revocation_url(&url);
+        let ocsp = server.ocsp_response(RevocationStatus::Revoked, Utc::now() + Duration::days(1));
+        serve_http(listener, vec![("/ocsp", ocsp)]);
+        let client = client_for(&server);
+
+        // server with key of revoked leaf places own certificate after it
+        // and staples a good response signed by that certificate
+        let (forged, stapled) =
+            server.forged_ocsp_response("hcc forged CA", RevocationStatus::Good);
+        let chain = vec![server.chain()[0].clone(), forged, server.chain()[1].clone()];
+        let mut config = server.server_config();
+        config
+            .set_single_cert_with_ocsp_and_sct(chain, server.key().clone(), stapled, vec![])
+            .unwrap();
+        let port = server.serve_forever(config);
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(Some(RevocationStatus::Unknown), result.revocation);
+
+        // actual issuer is found further in chain when querying
+        let client = builder_for(&server).query_revocation(true).build();
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Invalid(ErrorKind::Revoked), result.state);
+        assert_eq!(Some(RevocationStatus::Revoked), result.revocation);
+    }
+
+    #[tokio::test]
+    async fn test_query_revocation() {
+        let (listener, url) = http_listener();
+        let server = TestServer::with_revocation_url(&url);
+        let ocsp = server.ocsp_response(RevocationStatus::Revoked, Utc::now() + Duration::days(1));
+        serve_http(listener, vec![("/ocsp", ocsp)]);
+        let client = builder_for(&server).query_revocation(true).build();
+        let port = server.serve_forever(server.server_config());
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Invalid(ErrorKind::Revoked), result.state);
+        assert_eq!(Some(RevocationStatus::Revoked), result.revocation);
+
+        // CRL is downloaded when OCSP responder fails
+        let (listener, url) = http_listener();
+        let server = TestServer::with_revocation_url(&url);
+        serve_http(listener, vec![("/crl", server.crl(false))]);
+        let client = builder_for(&server).query_revocation(true).build();
+        let port = server.serve_forever(server.server_config());
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+        assert_eq!(Some(RevocationStatus::Good), result.revocation);
+
+        // neither is reachable
+        let (listener, url) = http_listener();
+        drop(listener);
+        let server = TestServer::with_revocation_url(&url);
+        let client = builder_for(&server).query_revocation(true).build();
+        let port = server.serve_forever(server.server_config());
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+        assert_eq!(Some(RevocationStatus::Unknown), result.revocation);
+        assert!(result.to_string().ends_with(", revocation status unknown"));
+    }
+
+    #[tokio::test]
+    async fn test_ct_policy() {
+        let log = TestLog::new("hcc test log");
+        let log_list = CtLogList::from_json(&TestLog::log_list(&[&log])).unwrap();
+        let server = TestServer::with_embedded_scts(&[&log]);
+        let client = builder_for(&server)
+            .ct_policy(CtPolicy {
+                min_logs: 2,
+                log_list: log_list.clone(),
+            })
+            .build();
+        let port = server.serve_forever(server.server_config());
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Warning, result.state);
+        assert_eq!(1, result.scts.len());
+        assert_eq!(SctSource::Embedded, result.scts[0].source);
+        assert!(result.scts[0].valid);
+        let violation = Some("SCTs from 1 of 2 required CT logs".to_string());
+        assert_eq!(violation, result.ct_violation);
+
+        let client = builder_for(&server)
+            .ct_policy(CtPolicy {
+                min_logs: 1,
+                log_list,
+            })
+            .build();
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+        assert_eq!(None, result.ct_violation);
+    }
+
+    #[tokio::test]
+    async fn test_delivered_scts() {
+        let log = TestLog::new("hcc test log");
+        let log_list = CtLogList::from_json(&TestLog::log_list(&[&log])).unwrap();
+        let server = TestServer::with_revocation_url("http://127.0.0.1:1");
+        let list = encode_list(&[log.sign_x509(&server.chain()[0].0)]);
+        let client = builder_for(&server)
+            .ct_policy(CtPolicy {
+                min_logs: 1,
+                log_list,
+            })
+            .build();
+
+        let mut config = server.server_config();
+        config.versions = vec![ProtocolVersion::TLSv1_2];
+        config
+            .set_single_cert_with_ocsp_and_sct(
+                server.chain().to_vec(),
+                server.key().clone(),
+                vec![],
+                list.clone(),
+            )
+            .unwrap();
+        let port = server.serve_forever(config);
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+        assert_eq!(1, result.scts.len());
+        assert_eq!(SctSource::TlsExtension, result.scts[0].source);
+
+        let mut config = server.server_config();
+        let ocsp = server.ocsp_response_with_scts(&list);
+        config
+            .set_single_cert_with_ocsp_and_sct(
+                server.chain().to_vec(),
+                server.key().clone(),
+                ocsp,
+                vec![],
+            )
+            .unwrap();
+        let port = server.serve_forever(config);
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+        assert_eq!(1, result.scts.len());
+        assert_eq!(SctSource::Ocsp, result.scts[0].source);
+
+        // without SCTs
+        let port = server.serve_forever(server.server_config());
+        let target = format!("localhost:{0}", port);
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Warning, result.state);
+        assert!(result.scts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pins() {
+        let server = TestServer::new();
+        let leaf = CertificateInfo::parse(&server.chain()[0].0, &Utc::now()).unwrap();
+        let dir = temp_dir("pins");
+        let ca_file = dir.join("ca.pem");
+        fs::write(&ca_file, server.ca_pem()).unwrap();
+        let other: Pin = "sha256/47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+            .parse()
+            .unwrap();
+        let port = server.serve_forever(server.server_config());
+        let target = format!("localhost:{0}", port);
+
+        let client = CheckClient::builder()
+            .ca_file(&ca_file)
+            .unwrap()
+            .pin("localhost", other.clone())
+            .pin("localhost", Pin::Spki(leaf.spki_fingerprint.clone()))
+            .build();
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+        assert_eq!(leaf.fingerprint, result.chain[0].fingerprint);
+
+        let client = CheckClient::builder()
+            .ca_file(&ca_file)
+            .unwrap()
+            .pin("localhost", other.clone())
+            .build();
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::PinMismatch, result.state);
+
+        // pins of other domain names and ports are ignored
+        let client = CheckClient::builder()
+            .ca_file(&ca_file)
+            .unwrap()
+            .pin("example.com", other.clone())
+            .pin(&format!("localhost:{0}", port + 1), other.clone())
+            .build();
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::Ok, result.state);
+
+        // pins of host and port take precedence over pins of host
+        let client = CheckClient::builder()
+            .ca_file(&ca_file)
+            .unwrap()
+            .pin("localhost", Pin::Spki(leaf.spki_fingerprint.clone()))
+            .pin(&target, other)
+            .build();
+        let result = client.check_certificate(&target).await.unwrap();
+        assert_eq!(CheckState::PinMismatch, result.state);
+    }
 }
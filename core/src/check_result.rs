@@ -1,26 +1,115 @@
 use std::fmt;
+use std::net::IpAddr;
 
 use chrono::{DateTime, TimeZone, Utc};
 use num_format::{Locale, ToFormattedString};
 use serde::{Deserialize, Serialize};
 use std::fmt::Formatter;
 
+use crate::certificate::CertificateInfo;
+use crate::ct::{Sct, SctJSON};
+use crate::protocol::Protocol;
+use crate::revocation::RevocationStatus;
+use crate::target::Target;
+
 /// State of Certificate
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub enum CheckState {
     /// Default state
+    #[default]
     Unknown,
     /// Certificate is valid
     Ok,
     /// Certificate is going to expire soon
     Warning,
+    /// Certificate is going to expire within critical threshold
+    Critical,
     /// Certificate expired
     Expired,
+    /// Certificate is not valid yet
+    NotYetValid,
+    /// Certificate is received but rejected for reason other than expiration
+    Invalid(ErrorKind),
+    /// Neither certificate nor public key in chain matches any pin of target
+    PinMismatch,
+    /// Certificate cannot be retrieved
+    Error(ErrorKind),
+}
+
+/// Reason why certificate is invalid or cannot be retrieved
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorKind {
+    /// Domain name cannot be resolved
+    Dns,
+    /// Connection is refused by server
+    ConnectionRefused,
+    /// Connection or handshake timed out
+    Timeout,
+    /// Connection is reset or closed unexpectedly
+    Connection,
+    /// Plaintext protocol failed to upgrade to TLS
+    Starttls,
+    /// Server sent fatal alert during handshake
+    HandshakeAlert,
+    /// Handshake failed for reason other than certificate
+    Handshake,
+    /// Certificate is not issued by trusted certificate authority
+    UntrustedIssuer,
+    /// Certificate is not valid for domain name
+    NameMismatch,
+    /// Certificate is malformed or rejected for other reason
+    BadCertificate,
+    /// Target cannot be parsed or is not a valid domain name
+    InvalidTarget,
+    /// Certificate file cannot be read
+    File,
+    /// Password of PKCS#12 archive is wrong
+    Password,
+    /// PKCS#12 archive is protected by encryption or MAC which is not supported
+    UnsupportedPkcs12,
+    /// Certificate is revoked by issuer
+    Revoked,
 }
 
-impl Default for CheckState {
-    fn default() -> Self {
-        CheckState::Unknown
+impl ErrorKind {
+    /// Whether the error may be transient so that check is worth retrying
+    ///
+    /// ```
+    /// # use hcc::ErrorKind;
+    /// assert!(ErrorKind::Timeout.is_retryable());
+    /// assert!(!ErrorKind::UntrustedIssuer.is_retryable());
+    /// ```
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ErrorKind::Dns
+                | ErrorKind::ConnectionRefused
+                | ErrorKind::Timeout
+                | ErrorKind::Connection
+        )
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ErrorKind::Dns => "DNS lookup failed",
+            ErrorKind::ConnectionRefused => "connection refused",
+            ErrorKind::Timeout => "timed out",
+            ErrorKind::Connection => "connection failed",
+            ErrorKind::Starttls => "STARTTLS failed",
+            ErrorKind::HandshakeAlert => "handshake alert received",
+            ErrorKind::Handshake => "handshake failed",
+            ErrorKind::UntrustedIssuer => "untrusted issuer",
+            ErrorKind::NameMismatch => "name mismatch",
+            ErrorKind::BadCertificate => "bad certificate",
+            ErrorKind::InvalidTarget => "invalid target",
+            ErrorKind::File => "file cannot be read",
+            ErrorKind::Password => "wrong password",
+            ErrorKind::UnsupportedPkcs12 => "unsupported PKCS#12 encryption",
+            ErrorKind::Revoked => "revoked",
+        };
+        write!(f, "{}", s)
     }
 }
 
@@ -30,7 +119,12 @@ impl fmt::Display for CheckState {
             CheckState::Unknown => write!(f, "Unknown"),
             CheckState::Ok => write!(f, "OK"),
             CheckState::Warning => write!(f, "WARNING"),
+            CheckState::Critical => write!(f, "CRITICAL"),
             CheckState::Expired => write!(f, "EXPIPRED"),
+            CheckState::NotYetValid => write!(f, "NOT YET VALID"),
+            CheckState::Invalid(_) => write!(f, "INVALID"),
+            CheckState::PinMismatch => write!(f, "PIN MISMATCH"),
+            CheckState::Error(_) => write!(f, "ERROR"),
         }
     }
 }
@@ -44,31 +138,113 @@ pub struct CheckResult<'a> {
     pub checked_at: i64,
     /// Remaining days to the expiration date
     pub days: i64,
+    /// Protocol spoken before TLS handshake
+    pub protocol: Protocol,
     /// Domain name that got checked
     pub domain_name: &'a str,
-    /// Exact expiration time in seconds since Unix epoch
+    /// Port that got checked
+    pub port: u16,
+    /// Start of validity period of leaf certificate in seconds since Unix epoch
+    pub not_before: i64,
+    /// Exact expiration time of the certificate which expires first in chain,
+    /// in seconds since Unix epoch
     pub not_after: i64,
     /// Elapsed time in milliseconds
     pub elapsed: Option<u128>,
+    /// Number of attempts made to check
+    pub attempts: u32,
+    /// IP address that got checked when it is given or in all addresses mode
+    pub address: Option<IpAddr>,
+    /// Other addresses of the same target serve different certificates
+    pub inconsistent: bool,
+    /// Certificates presented by server, leaf certificate first
+    pub chain: Vec<CertificateInfo>,
+    /// Whether domain name is covered by leaf certificate, None if no certificate is received
+    pub name_matched: Option<bool>,
+    /// Reason why certificate is not trusted, only reported in expiry only mode
+    pub untrusted: Option<ErrorKind>,
+    /// Weak keys or signatures in chain, only reported with crypto policy
+    pub weak_crypto: Vec<String>,
+    /// Negotiated protocol version, e.g. TLSv1.3
+    pub tls_version: Option<String>,
+    /// Negotiated cipher suite, e.g. TLS13_AES_256_GCM_SHA384
+    pub cipher_suite: Option<String>,
+    /// Protocol negotiated with ALPN, e.g. h2
+    pub alpn: Option<String>,
+    /// Revocation status of leaf certificate, None if it is neither stapled nor queried
+    pub revocation: Option<RevocationStatus>,
+    /// Signed certificate timestamps of leaf certificate, only reported with CT policy
+    pub scts: Vec<Sct>,
+    /// Reason why CT policy is not met
+    pub ct_violation: Option<String>,
 }
 
 impl<'a> CheckResult<'a> {
-    /// Create a result from expired domain name and when the check occurred
+    /// Create a result from target which cannot be checked and when the check occurred
+    ///
+    /// ```
+    /// # use hcc::{CheckResult, ErrorKind, Target};
+    /// use chrono::Utc;
+    /// let target = Target::parse("nonexistent.invalid").unwrap();
+    /// CheckResult::error(&target, ErrorKind::Dns, &Utc::now());
+    /// ```
+    pub fn error(target: &Target<'a>, kind: ErrorKind, checked_at: &'a DateTime<Utc>) -> Self {
+        CheckResult {
+            state: CheckState::Error(kind),
+            checked_at: checked_at.timestamp(),
+            protocol: target.protocol,
+            domain_name: target.host,
+            port: target.port,
+            ..Default::default()
+        }
+    }
+
+    /// Create a result from target which cannot be parsed and when the check occurred
     ///
     /// ```
     /// # use hcc::CheckResult;
     /// use chrono::Utc;
-    /// CheckResult::expired("expired.badssl.com", &Utc::now());
+    /// let now = Utc::now();
+    /// let result = CheckResult::invalid_target("example.com:https", &now);
+    /// assert_eq!("example.com:https", result.target());
     /// ```
-    pub fn expired(domain_name: &'a str, checked_at: &'a DateTime<Utc>) -> Self {
+    pub fn invalid_target(target: &'a str, checked_at: &'a DateTime<Utc>) -> Self {
         CheckResult {
-            state: CheckState::Expired,
+            state: CheckState::Error(ErrorKind::InvalidTarget),
             checked_at: checked_at.timestamp(),
-            domain_name,
+            domain_name: target,
             ..Default::default()
         }
     }
 
+    /// Protocol, domain name and port that got checked e.g. example.com:443 or smtp://example.com:25
+    ///
+    /// ```
+    /// # use hcc::CheckResult;
+    /// let result = CheckResult {
+    ///     domain_name: "example.com",
+    ///     port: 8443,
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!("example.com:8443", result.target());
+    /// ```
+    pub fn target(&self) -> String {
+        // port is unknown when target cannot be parsed
+        if self.port == 0 {
+            return self.domain_name.to_string();
+        }
+        let target = Target {
+            protocol: self.protocol,
+            host: self.domain_name,
+            port: self.port,
+            address: None,
+        };
+        match self.address {
+            Some(address) => format!("{0} ({1})", target, address),
+            None => target.to_string(),
+        }
+    }
+
     /// Expiration date of certficate in RFC3339 format
     ///
     /// ```
@@ -80,6 +256,17 @@ impl<'a> CheckResult<'a> {
         Utc.timestamp(self.not_after, 0).to_rfc3339()
     }
 
+    /// Start of validity period of certificate in RFC3339 format
+    ///
+    /// ```
+    /// # use hcc::CheckResult;
+    /// let result = CheckResult::default();
+    /// result.not_before_timestamp();
+    /// ```
+    pub fn not_before_timestamp(&self) -> String {
+        Utc.timestamp(self.not_before, 0).to_rfc3339()
+    }
+
     /// Human-readable sentence of certificate state
     ///
     /// ```
@@ -88,26 +275,108 @@ impl<'a> CheckResult<'a> {
     /// result.sentence();
     /// ```
     pub fn sentence(&self) -> String {
+        let mut sentence = self.state_sentence();
+        if self.name_matched == Some(false) && !self.subject_alt_names().is_empty() {
+            let names = self.subject_alt_names().join(", ");
+            sentence.push_str(&format!(", certificate covers {0}", names));
+        }
+        if let Some(certificate) = self.expiring_intermediate() {
+            sentence.push_str(&format!(", earliest by {0}", certificate.subject));
+        }
+        if self.inconsistent {
+            sentence.push_str(", certificate differs between addresses");
+        }
+        if let Some(kind) = self.untrusted {
+            sentence.push_str(&format!(", untrusted: {0}", kind));
+        }
+        if !self.weak_crypto.is_empty() {
+            let weak_crypto = self.weak_crypto.join(", ");
+            sentence.push_str(&format!(", weak crypto: {0}", weak_crypto));
+        }
+        if self.revocation == Some(RevocationStatus::Unknown) {
+            sentence.push_str(", revocation status unknown");
+        }
+        if let Some(ref violation) = self.ct_violation {
+            sentence.push_str(&format!(", {0}", violation));
+        }
+        sentence
+    }
+
+    /// DNS names and IP addresses covered by leaf certificate
+    ///
+    /// ```
+    /// # use hcc::CheckResult;
+    /// let result = CheckResult::default();
+    /// assert!(result.subject_alt_names().is_empty());
+    /// ```
+    pub fn subject_alt_names(&self) -> &[String] {
+        self.chain
+            .first()
+            .map_or(&[], |leaf| leaf.subject_alt_names.as_slice())
+    }
+
+    /// Common name of leaf certificate
+    ///
+    /// ```
+    /// # use hcc::CheckResult;
+    /// let result = CheckResult::default();
+    /// assert_eq!(None, result.common_name());
+    /// ```
+    pub fn common_name(&self) -> Option<&str> {
+        self.chain
+            .first()
+            .and_then(|leaf| leaf.common_name.as_deref())
+    }
+
+    /// Certificate other than leaf certificate which expires before the leaf certificate
+    fn expiring_intermediate(&self) -> Option<&CertificateInfo> {
+        let (leaf, rest) = self.chain.split_first()?;
+        rest.iter()
+            .filter(|certificate| certificate.not_after < leaf.not_after)
+            .min_by_key(|certificate| certificate.not_after)
+    }
+
+    fn state_sentence(&self) -> String {
         let days = self.days.to_formatted_string(&Locale::en);
+        let target = self.target();
         match self.state {
-            CheckState::Unknown => format!("certificate state of {} is unknown", self.domain_name),
+            CheckState::Unknown => format!("certificate state of {} is unknown", target),
             CheckState::Ok => format!(
                 "certificate of {} expires in {} days ({})",
-                self.domain_name,
+                target,
                 days,
                 self.not_after_timestamp()
             ),
-            CheckState::Warning => format!(
+            CheckState::Warning | CheckState::Critical => format!(
                 "certificate of {} expires in {} days ({})",
-                self.domain_name,
+                target,
                 days,
                 self.not_after_timestamp()
             ),
             CheckState::Expired => format!(
                 "certificate of {} has expired ({})",
-                self.domain_name,
+                target,
+                self.not_after_timestamp()
+            ),
+            CheckState::NotYetValid => format!(
+                "certificate of {} is not valid until {}",
+                target,
+                self.not_before_timestamp()
+            ),
+            CheckState::Invalid(kind) => format!(
+                "certificate of {} is invalid: {} ({})",
+                target,
+                kind,
                 self.not_after_timestamp()
             ),
+            CheckState::PinMismatch => format!(
+                "certificate of {} does not match pinned fingerprints ({})",
+                target,
+                self.not_after_timestamp()
+            ),
+            CheckState::Error(kind) => {
+                format!("failed to check certificate of {}: {}", target, kind)
+            }
         }
     }
 
@@ -142,13 +411,30 @@ impl<'a> CheckResult<'a> {
                     "[-]"
                 }
             }
-            CheckState::Expired => {
+            CheckState::Critical => {
+                if unicode {
+                    "\u{1f6a8}"
+                } else {
+                    "[*]"
+                }
+            }
+            CheckState::Expired
+            | CheckState::NotYetValid
+            | CheckState::Invalid(_)
+            | CheckState::PinMismatch => {
                 if unicode {
                     "\u{274c}"
                 } else {
                     "[x]"
                 }
             }
+            CheckState::Error(_) => {
+                if unicode {
+                    "\u{2757}"
+                } else {
+                    "[!]"
+                }
+            }
         };
         s.to_string()
     }
@@ -160,12 +446,22 @@ impl<'a> fmt::Display for CheckResult<'a> {
 
         s.push_str(&self.state_icon(false));
 
-        s.push_str(&" ");
+        s.push(' ');
 
         s.push_str(&self.sentence());
 
         if let Some(elapsed) = self.elapsed {
             s.push_str(&format!(", {0}ms elapsed", elapsed));
+            match self.attempts {
+                0 | 1 => s.push_str(&format!(", {0} attempt", self.attempts)),
+                _ => s.push_str(&format!(", {0} attempts", self.attempts)),
+            }
+            if let (Some(version), Some(suite)) = (&self.tls_version, &self.cipher_suite) {
+                s.push_str(&format!(", {0} {1}", version, suite));
+            }
+            if let Some(ref alpn) = self.alpn {
+                s.push_str(&format!(", ALPN {0}", alpn));
+            }
         }
 
         write!(f, "{}", s)
@@ -181,12 +477,120 @@ pub struct CheckResultJSON {
     pub checked_at: String,
     /// Remaining days to the expiration date
     pub days: i64,
+    /// Protocol spoken before TLS handshake
+    pub protocol: String,
     /// Domain name that got checked
     pub domain_name: String,
+    /// Port that got checked
+    pub port: u16,
+    /// Start of validity period in RFC3389 format
+    pub not_before: String,
     /// Expiration time in RFC3389 format
     pub expired_at: String,
     /// Elapsed time in milliseconds
     pub elapsed: u128,
+    /// Number of attempts made to check
+    pub attempts: u32,
+    /// IP address that got checked when it is given or in all addresses mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// Other addresses of the same target serve different certificates
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub inconsistent: bool,
+    /// Reason why certificate is invalid or cannot be retrieved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Certificates presented by server, leaf certificate first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chain: Vec<CertificateInfoJSON>,
+    /// DNS names and IP addresses covered by leaf certificate
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subject_alt_names: Vec<String>,
+    /// Common name of leaf certificate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub common_name: Option<String>,
+    /// Whether domain name is covered by leaf certificate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_matched: Option<bool>,
+    /// Reason why certificate is not trusted, only reported in expiry only mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub untrusted: Option<String>,
+    /// Public key algorithm and size of leaf certificate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_algorithm: Option<String>,
+    /// Signature algorithm of leaf certificate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature_algorithm: Option<String>,
+    /// SHA-256 fingerprint of leaf certificate in hexadecimal separated by colon
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// SHA-256 fingerprint of public key of leaf certificate in base64
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spki_fingerprint: Option<String>,
+    /// Weak keys or signatures in chain, only reported with crypto policy
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weak_crypto: Vec<String>,
+    /// Negotiated protocol version
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_version: Option<String>,
+    /// Negotiated cipher suite
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher_suite: Option<String>,
+    /// Protocol negotiated with ALPN
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpn: Option<String>,
+    /// Revocation status of leaf certificate, GOOD, REVOKED or UNKNOWN
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revocation: Option<String>,
+    /// Signed certificate timestamps of leaf certificate
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scts: Vec<SctJSON>,
+    /// Reason why CT policy is not met
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ct_violation: Option<String>,
+}
+
+/// Certificate in chain in JSON format
+#[derive(Default, Serialize, Deserialize)]
+pub struct CertificateInfoJSON {
+    /// Distinguished name of subject
+    pub subject: String,
+    /// Distinguished name of issuer
+    pub issuer: String,
+    /// Serial number in hexadecimal separated by colon
+    pub serial: String,
+    /// Start of validity period in RFC3389 format
+    pub not_before: String,
+    /// End of validity period in RFC3389 format
+    pub not_after: String,
+    /// Remaining days to the expiration date
+    pub days: i64,
+    /// Public key algorithm and size e.g. RSA-2048, P-256 or Ed25519
+    pub key_algorithm: String,
+    /// Algorithm of signature by issuer
+    pub signature_algorithm: String,
+    /// SHA-256 fingerprint in hexadecimal separated by colon
+    pub fingerprint: String,
+    /// SHA-256 fingerprint of public key in base64
+    pub spki_fingerprint: String,
+}
+
+impl CertificateInfoJSON {
+    /// Convert certificate in chain to JSON
+    pub fn new(certificate: &CertificateInfo) -> CertificateInfoJSON {
+        CertificateInfoJSON {
+            subject: certificate.subject.clone(),
+            issuer: certificate.issuer.clone(),
+            serial: certificate.serial.clone(),
+            not_before: Utc.timestamp(certificate.not_before, 0).to_rfc3339(),
+            not_after: Utc.timestamp(certificate.not_after, 0).to_rfc3339(),
+            days: certificate.days,
+            key_algorithm: certificate.key_algorithm.clone(),
+            signature_algorithm: certificate.signature_algorithm.clone(),
+            fingerprint: certificate.fingerprint.clone(),
+            spki_fingerprint: certificate.spki_fingerprint.clone(),
+        }
+    }
 }
 
 impl CheckResultJSON {
@@ -206,10 +610,42 @@ impl CheckResultJSON {
         CheckResultJSON {
             state: result.state.to_string(),
             days: result.days,
+            protocol: result.protocol.to_string(),
             domain_name: result.domain_name.to_string(),
+            port: result.port,
             checked_at: Utc.timestamp(result.checked_at, 0).to_rfc3339(),
-            expired_at: Utc.timestamp(result.not_after, 0).to_rfc3339(),
+            not_before: result.not_before_timestamp(),
+            expired_at: result.not_after_timestamp(),
             elapsed: result.elapsed.unwrap_or(0),
+            attempts: result.attempts,
+            address: result.address.map(|address| address.to_string()),
+            inconsistent: result.inconsistent,
+            error: match result.state {
+                CheckState::Invalid(kind) | CheckState::Error(kind) => Some(kind.to_string()),
+                _ => None,
+            },
+            chain: result.chain.iter().map(CertificateInfoJSON::new).collect(),
+            subject_alt_names: result.subject_alt_names().to_vec(),
+            common_name: result.common_name().map(String::from),
+            name_matched: result.name_matched,
+            untrusted: result.untrusted.map(|kind| kind.to_string()),
+            key_algorithm: result.chain.first().map(|leaf| leaf.key_algorithm.clone()),
+            signature_algorithm: result
+                .chain
+                .first()
+                .map(|leaf| leaf.signature_algorithm.clone()),
+            fingerprint: result.chain.first().map(|leaf| leaf.fingerprint.clone()),
+            spki_fingerprint: result
+                .chain
+                .first()
+                .map(|leaf| leaf.spki_fingerprint.clone()),
+            weak_crypto: result.weak_crypto.clone(),
+            tls_version: result.tls_version.clone(),
+            cipher_suite: result.cipher_suite.clone(),
+            alpn: result.alpn.clone(),
+            revocation: result.revocation.map(|status| status.to_string()),
+            scts: result.scts.iter().map(SctJSON::new).collect(),
+            ct_violation: result.ct_violation.clone(),
         }
     }
 }
@@ -218,8 +654,12 @@ impl CheckResultJSON {
 mod test {
     use chrono::{Duration, SubsecRound, TimeZone, Utc};
 
-    use crate::check_result::CheckState;
-    use crate::CheckResult;
+    use crate::certificate::CertificateInfo;
+    use crate::check_result::{CheckState, ErrorKind};
+    use crate::ct::{Sct, SctSource};
+    use crate::protocol::Protocol;
+    use crate::revocation::RevocationStatus;
+    use crate::{CheckResult, CheckResultJSON};
 
     fn build_result<'a>() -> CheckResult<'a> {
         let days = 512;
@@ -228,7 +668,8 @@ mod test {
         CheckResult {
             checked_at: now.timestamp(),
             days,
-            domain_name: &"example.com",
+            domain_name: "example.com",
+            port: 443,
             not_after: expired_at.timestamp(),
             ..Default::default()
         }
@@ -241,7 +682,7 @@ mod test {
 
         let left = format!("{0}", result);
         let right = format!(
-            "[v] certificate of example.com expires in 512 days ({0})",
+            "[v] certificate of example.com:443 expires in 512 days ({0})",
             Utc.timestamp(result.not_after, 0).to_rfc3339()
         );
         assert_eq!(left, right);
@@ -253,10 +694,23 @@ mod test {
         result.state = CheckState::Warning;
         let left = format!("{0}", result);
         let right = format!(
-            "[-] certificate of example.com expires in 512 days ({0})",
+            "[-] certificate of example.com:443 expires in 512 days ({0})",
+            Utc.timestamp(result.not_after, 0).to_rfc3339()
+        );
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_display_critical() {
+        let mut result = build_result();
+        result.state = CheckState::Critical;
+        let left = format!("{0}", result);
+        let right = format!(
+            "[*] certificate of example.com:443 expires in 512 days ({0})",
             Utc.timestamp(result.not_after, 0).to_rfc3339()
         );
         assert_eq!(left, right);
+        assert_eq!("CRITICAL", CheckResultJSON::new(&result).state);
     }
 
     #[test]
@@ -265,9 +719,309 @@ mod test {
         result.state = CheckState::Expired;
         let left = format!("{0}", result);
         let right = format!(
-            "[x] certificate of example.com has expired ({})",
+            "[x] certificate of example.com:443 has expired ({})",
+            Utc.timestamp(result.not_after, 0).to_rfc3339()
+        );
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_display_port() {
+        let mut result = build_result();
+        result.state = CheckState::Ok;
+        result.port = 8443;
+        let left = format!("{0}", result);
+        let right = format!(
+            "[v] certificate of example.com:8443 expires in 512 days ({0})",
+            Utc.timestamp(result.not_after, 0).to_rfc3339()
+        );
+        assert_eq!(left, right);
+
+        result.domain_name = "::1";
+        let left = format!("{0}", result);
+        let right = format!(
+            "[v] certificate of [::1]:8443 expires in 512 days ({0})",
+            Utc.timestamp(result.not_after, 0).to_rfc3339()
+        );
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_display_protocol() {
+        let mut result = build_result();
+        result.state = CheckState::Ok;
+        result.protocol = Protocol::Smtp;
+        result.port = 587;
+        let left = format!("{0}", result);
+        let right = format!(
+            "[v] certificate of smtp://example.com:587 expires in 512 days ({0})",
             Utc.timestamp(result.not_after, 0).to_rfc3339()
         );
         assert_eq!(left, right);
     }
+
+    #[test]
+    fn test_display_not_yet_valid() {
+        let mut result = build_result();
+        result.state = CheckState::NotYetValid;
+        result.not_before = result.checked_at + 86400;
+        let left = format!("{0}", result);
+        let right = format!(
+            "[x] certificate of example.com:443 is not valid until {0}",
+            Utc.timestamp(result.not_before, 0).to_rfc3339()
+        );
+        assert_eq!(left, right);
+
+        let json = CheckResultJSON::new(&result);
+        assert_eq!("NOT YET VALID", json.state);
+        assert_eq!(result.not_before_timestamp(), json.not_before);
+    }
+
+    #[test]
+    fn test_display_invalid() {
+        let mut result = build_result();
+        result.state = CheckState::Invalid(ErrorKind::UntrustedIssuer);
+        let left = format!("{0}", result);
+        let right = format!(
+            "[x] certificate of example.com:443 is invalid: untrusted issuer ({0})",
+            Utc.timestamp(result.not_after, 0).to_rfc3339()
+        );
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_display_error() {
+        let mut result = build_result();
+        result.state = CheckState::Error(ErrorKind::ConnectionRefused);
+        let left = format!("{0}", result);
+        assert_eq!(
+            left,
+            "[!] failed to check certificate of example.com:443: connection refused"
+        );
+    }
+
+    #[test]
+    fn test_display_attempts() {
+        let mut result = build_result();
+        result.state = CheckState::Error(ErrorKind::Timeout);
+        result.elapsed = Some(42);
+        result.attempts = 3;
+        let left = format!("{0}", result);
+        assert_eq!(
+            left,
+            "[!] failed to check certificate of example.com:443: timed out, 42ms elapsed, 3 attempts"
+        );
+
+        result.attempts = 1;
+        let left = format!("{0}", result);
+        assert!(left.ends_with(", 42ms elapsed, 1 attempt"));
+    }
+
+    #[test]
+    fn test_display_negotiated() {
+        let mut result = build_result();
+        result.state = CheckState::Ok;
+        result.elapsed = Some(42);
+        result.attempts = 1;
+        result.tls_version = Some("TLSv1.3".into());
+        result.cipher_suite = Some("TLS13_AES_256_GCM_SHA384".into());
+        result.alpn = Some("h2".into());
+        let left = format!("{0}", result);
+        assert!(left.ends_with(", 1 attempt, TLSv1.3 TLS13_AES_256_GCM_SHA384, ALPN h2"));
+
+        let json = CheckResultJSON::new(&result);
+        assert_eq!(Some("TLSv1.3".to_string()), json.tls_version);
+        assert_eq!(Some("h2".to_string()), json.alpn);
+
+        // details are only shown in verbose mode
+        result.elapsed = None;
+        assert!(!format!("{0}", result).contains("TLSv1.3"));
+    }
+
+    #[test]
+    fn test_display_revocation() {
+        let mut result = build_result();
+        result.state = CheckState::Invalid(ErrorKind::Revoked);
+        result.revocation = Some(RevocationStatus::Revoked);
+        let left = format!("{0}", result);
+        let right = format!(
+            "[x] certificate of example.com:443 is invalid: revoked ({0})",
+            Utc.timestamp(result.not_after, 0).to_rfc3339()
+        );
+        assert_eq!(left, right);
+        let json = CheckResultJSON::new(&result);
+        assert_eq!(Some("REVOKED".to_string()), json.revocation);
+
+        result.state = CheckState::Ok;
+        result.revocation = Some(RevocationStatus::Unknown);
+        assert!(format!("{0}", result).ends_with(", revocation status unknown"));
+        result.revocation = None;
+        let json = CheckResultJSON::new(&result);
+        let json = serde_json::to_string(&json).unwrap();
+        assert!(!json.contains("revocation"));
+    }
+
+    #[test]
+    fn test_display_scts() {
+        let mut result = build_result();
+        result.state = CheckState::Warning;
+        result.scts = vec![Sct {
+            log_id: "AAAA".into(),
+            log: Some("hcc test log".into()),
+            timestamp: 1_600_000_000_123,
+            source: SctSource::Embedded,
+            valid: true,
+        }];
+        result.ct_violation = Some("SCTs from 1 of 2 required CT logs".into());
+        let left = format!("{0}", result);
+        assert!(left.ends_with(", SCTs from 1 of 2 required CT logs"));
+
+        let json = CheckResultJSON::new(&result);
+        assert_eq!(1, json.scts.len());
+        assert_eq!("embedded", json.scts[0].source);
+        assert_eq!("2020-09-13T12:26:40.123+00:00", json.scts[0].timestamp);
+        assert!(json.scts[0].valid);
+    }
+
+    #[test]
+    fn test_display_pin_mismatch() {
+        let mut result = build_result();
+        result.state = CheckState::PinMismatch;
+        result.chain = vec![CertificateInfo {
+            fingerprint: "e3:b0".into(),
+            spki_fingerprint: "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=".into(),
+            ..Default::default()
+        }];
+        let left = format!("{0}", result);
+        let right = format!(
+            "[x] certificate of example.com:443 does not match pinned fingerprints ({0})",
+            Utc.timestamp(result.not_after, 0).to_rfc3339()
+        );
+        assert_eq!(left, right);
+
+        let json = CheckResultJSON::new(&result);
+        assert_eq!("PIN MISMATCH", json.state);
+        assert_eq!(None, json.error);
+        assert_eq!(Some("e3:b0".to_string()), json.fingerprint);
+        assert_eq!("e3:b0", json.chain[0].fingerprint);
+    }
+
+    #[test]
+    fn test_display_address() {
+        let mut result = build_result();
+        result.state = CheckState::Ok;
+        result.address = Some("2001:db8::1".parse().unwrap());
+        result.inconsistent = true;
+        let left = format!("{0}", result);
+        let right = format!(
+            "[v] certificate of example.com:443 (2001:db8::1) expires in 512 days ({0}), certificate differs between addresses",
+            Utc.timestamp(result.not_after, 0).to_rfc3339()
+        );
+        assert_eq!(left, right);
+
+        let json = CheckResultJSON::new(&result);
+        assert_eq!(Some("2001:db8::1".to_string()), json.address);
+        assert!(json.inconsistent);
+    }
+
+    #[test]
+    fn test_display_expiring_intermediate() {
+        let mut result = build_result();
+        result.state = CheckState::Warning;
+        result.chain = vec![
+            CertificateInfo {
+                subject: "CN=example.com".to_string(),
+                not_after: result.not_after + 86400,
+                ..Default::default()
+            },
+            CertificateInfo {
+                subject: "CN=Intermediate CA".to_string(),
+                not_after: result.not_after,
+                ..Default::default()
+            },
+        ];
+        let left = format!("{0}", result);
+        let right = format!(
+            "[-] certificate of example.com:443 expires in 512 days ({0}), earliest by CN=Intermediate CA",
+            Utc.timestamp(result.not_after, 0).to_rfc3339()
+        );
+        assert_eq!(left, right);
+
+        let json = CheckResultJSON::new(&result);
+        assert_eq!(2, json.chain.len());
+        assert_eq!("CN=Intermediate CA", json.chain[1].subject);
+        assert_eq!(result.not_after_timestamp(), json.chain[1].not_after);
+    }
+
+    #[test]
+    fn test_display_name_mismatch() {
+        let mut result = build_result();
+        result.state = CheckState::Invalid(ErrorKind::NameMismatch);
+        result.name_matched = Some(false);
+        result.chain = vec![CertificateInfo {
+            common_name: Some("example.net".to_string()),
+            subject_alt_names: vec!["example.net".to_string(), "*.example.net".to_string()],
+            ..Default::default()
+        }];
+        let left = format!("{0}", result);
+        let right = format!(
+            "[x] certificate of example.com:443 is invalid: name mismatch ({0}), certificate covers example.net, *.example.net",
+            Utc.timestamp(result.not_after, 0).to_rfc3339()
+        );
+        assert_eq!(left, right);
+
+        let json = CheckResultJSON::new(&result);
+        assert_eq!(Some("name mismatch".to_string()), json.error);
+        assert_eq!(2, json.subject_alt_names.len());
+        assert_eq!(Some("example.net".to_string()), json.common_name);
+        assert_eq!(Some(false), json.name_matched);
+    }
+
+    #[test]
+    fn test_display_untrusted() {
+        let mut result = build_result();
+        result.state = CheckState::Ok;
+        result.untrusted = Some(ErrorKind::UntrustedIssuer);
+        let left = format!("{0}", result);
+        let right = format!(
+            "[v] certificate of example.com:443 expires in 512 days ({0}), untrusted: untrusted issuer",
+            Utc.timestamp(result.not_after, 0).to_rfc3339()
+        );
+        assert_eq!(left, right);
+
+        let json = CheckResultJSON::new(&result);
+        assert_eq!(Some("untrusted issuer".to_string()), json.untrusted);
+    }
+
+    #[test]
+    fn test_display_weak_crypto() {
+        let mut result = build_result();
+        result.state = CheckState::Warning;
+        result.weak_crypto = vec![
+            "RSA-1024 key".to_string(),
+            "sha1WithRSAEncryption signature of CN=intermediate".to_string(),
+        ];
+        let left = format!("{0}", result);
+        let right = format!(
+            "[-] certificate of example.com:443 expires in 512 days ({0}), weak crypto: RSA-1024 key, sha1WithRSAEncryption signature of CN=intermediate",
+            Utc.timestamp(result.not_after, 0).to_rfc3339()
+        );
+        assert_eq!(left, right);
+
+        let json = CheckResultJSON::new(&result);
+        assert_eq!(2, json.weak_crypto.len());
+    }
+
+    #[test]
+    fn test_json_error() {
+        let mut result = build_result();
+        result.state = CheckState::Error(ErrorKind::Dns);
+        let json = CheckResultJSON::new(&result);
+        assert_eq!("ERROR", json.state);
+        assert_eq!(Some("DNS lookup failed".to_string()), json.error);
+
+        result.state = CheckState::Ok;
+        let json = CheckResultJSON::new(&result);
+        assert_eq!(None, json.error);
+    }
 }
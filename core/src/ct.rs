@@ -0,0 +1,696 @@
+//! Signed certificate timestamps of Certificate Transparency logs
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use anyhow::{bail, Context as _};
+use chrono::{TimeZone, Utc};
+use ring::{digest, signature};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::der::{
+    der, expect_der, read_der, TAG_BIT_STRING, TAG_BOOLEAN, TAG_CONTEXT_0, TAG_CONTEXT_3,
+    TAG_ENUMERATED, TAG_OCTET_STRING, TAG_OID, TAG_SEQUENCE,
+};
+
+/// Embedded SCT list extension of certificate, 1.3.6.1.4.1.11129.2.4.2
+pub(crate) const OID_EMBEDDED_SCTS: &[u8] =
+    &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xd6, 0x79, 0x02, 0x04, 0x02];
+/// SCT list extension of OCSP response, 1.3.6.1.4.1.11129.2.4.5
+pub(crate) const OID_OCSP_SCTS: &[u8] =
+    &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xd6, 0x79, 0x02, 0x04, 0x05];
+/// id-ecPublicKey, 1.2.840.10045.2.1
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+/// rsaEncryption, 1.2.840.113549.1.1.1
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+
+/// Entry type of certificate sent in TLS extension or OCSP response
+const X509_ENTRY: u16 = 0;
+/// Entry type of certificate with embedded SCTs
+const PRECERT_ENTRY: u16 = 1;
+
+/// signed_certificate_timestamp extension of TLS
+const EXTENSION_SCT: u16 = 0x0012;
+
+/// Maximum bytes recorded from server, enough for ServerHello
+const RECORDING_LIMIT: usize = 16 * 1024;
+
+/// Certificate Transparency log trusted to sign timestamps
+#[derive(Clone, Debug, PartialEq)]
+pub struct CtLog {
+    /// Description of log e.g. Google 'Argon2024' log
+    pub description: String,
+    /// SHA-256 hash of public key of log
+    pub id: Vec<u8>,
+    /// Public key of log in SubjectPublicKeyInfo format
+    pub key: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct LogListFile {
+    operators: Vec<OperatorFile>,
+}
+
+#[derive(Deserialize)]
+struct OperatorFile {
+    logs: Vec<LogFile>,
+}
+
+#[derive(Deserialize)]
+struct LogFile {
+    description: String,
+    log_id: String,
+    key: String,
+}
+
+/// Known Certificate Transparency logs, read from a file so that no network is needed
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CtLogList {
+    /// Logs of every operator
+    pub logs: Vec<CtLog>,
+}
+
+impl CtLogList {
+    /// Read log list in JSON format published by Chrome, version 3, e.g. log_list.json
+    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("failed to read log list {0}", path.display()))?;
+        CtLogList::from_json(&json)
+            .with_context(|| format!("failed to parse log list {0}", path.display()))
+    }
+
+    /// Parse log list in JSON format published by Chrome, version 3
+    ///
+    /// ```
+    /// # use hcc::CtLogList;
+    /// let json = r#"{"operators": [{"name": "Example", "logs": []}]}"#;
+    /// assert!(CtLogList::from_json(json).unwrap().logs.is_empty());
+    /// ```
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let file: LogListFile = serde_json::from_str(json)?;
+        let mut logs = vec![];
+        for log in file
+            .operators
+            .into_iter()
+            .flat_map(|operator| operator.logs)
+        {
+            let id = base64::decode(&log.log_id)?;
+            if id.len() != 32 {
+                bail!("log ID of {0} is not 32 bytes", log.description);
+            }
+            logs.push(CtLog {
+                description: log.description,
+                id,
+                key: base64::decode(&log.key)?,
+            });
+        }
+        Ok(CtLogList { logs })
+    }
+
+    fn find(&self, id: &[u8]) -> Option<&CtLog> {
+        self.logs.iter().find(|log| log.id == id)
+    }
+}
+
+/// Minimum number of distinct logs which must sign timestamps of leaf certificate
+#[derive(Clone, Debug, PartialEq)]
+pub struct CtPolicy {
+    /// Minimum number of distinct logs with valid timestamps, 2 by default
+    pub min_logs: usize,
+    /// Logs used to verify timestamps
+    pub log_list: CtLogList,
+}
+
+impl Default for CtPolicy {
+    fn default() -> Self {
+        CtPolicy {
+            min_logs: 2,
+            log_list: CtLogList::default(),
+        }
+    }
+}
+
+impl CtPolicy {
+    /// Reason why policy is not met, None if enough logs signed valid timestamps
+    pub(crate) fn violation(&self, scts: &[Sct]) -> Option<String> {
+        let logs: HashSet<&str> = scts
+            .iter()
+            .filter(|sct| sct.valid)
+            .map(|sct| sct.log_id.as_str())
+            .collect();
+        if logs.len() >= self.min_logs {
+            return None;
+        }
+        Some(format!(
+            "SCTs from {0} of {1} required CT logs",
+            logs.len(),
+            self.min_logs
+        ))
+    }
+}
+
+/// Where signed certificate timestamp is delivered
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SctSource {
+    /// Extension of leaf certificate
+    Embedded,
+    /// Extension of TLS handshake, only visible with TLS 1.2 since TLS 1.3 encrypts it
+    TlsExtension,
+    /// Extension of OCSP response stapled by server
+    Ocsp,
+}
+
+impl fmt::Display for SctSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SctSource::Embedded => write!(f, "embedded"),
+            SctSource::TlsExtension => write!(f, "TLS extension"),
+            SctSource::Ocsp => write!(f, "OCSP"),
+        }
+    }
+}
+
+/// Signed certificate timestamp of leaf certificate
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sct {
+    /// ID of log in base64
+    pub log_id: String,
+    /// Description of log, None if log is not in log list
+    pub log: Option<String>,
+    /// When log promised to include certificate, in milliseconds since Unix epoch
+    pub timestamp: i64,
+    /// Where timestamp is delivered
+    pub source: SctSource,
+    /// Whether timestamp is signed by a known log and is not in the future
+    pub valid: bool,
+}
+
+/// Signed certificate timestamp in JSON format
+#[derive(Serialize, Deserialize)]
+pub struct SctJSON {
+    /// ID of log in base64
+    pub log_id: String,
+    /// Description of log
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log: Option<String>,
+    /// Timestamp in RFC3339 format
+    pub timestamp: String,
+    /// embedded, TLS extension or OCSP
+    pub source: String,
+    /// Whether timestamp is signed by a known log and is not in the future
+    pub valid: bool,
+}
+
+impl SctJSON {
+    /// Convert timestamp to JSON
+    pub fn new(sct: &Sct) -> Self {
+        SctJSON {
+            log_id: sct.log_id.clone(),
+            log: sct.log.clone(),
+            timestamp: Utc.timestamp_millis(sct.timestamp).to_rfc3339(),
+            source: sct.source.to_string(),
+            valid: sct.valid,
+        }
+    }
+}
+
+/// Timestamps of leaf certificate from every source, verified with logs in list at now,
+/// in seconds since Unix epoch
+pub(crate) fn scts(
+    chain: &[rustls::Certificate],
+    received: &[u8],
+    ocsp_response: &[u8],
+    log_list: &CtLogList,
+    now: i64,
+) -> Vec<Sct> {
+    let (leaf, issuer) = match chain {
+        [leaf, rest @ ..] => (leaf.as_ref(), rest.first().map(|issuer| issuer.as_ref())),
+        _ => return vec![],
+    };
+    let mut scts = vec![];
+    if let Some(list) = embedded_scts(leaf) {
+        // embedded timestamps sign the certificate before they are embedded
+        let entry = issuer.and_then(|issuer| precert_entry(leaf, issuer));
+        for sct in split_list(&list) {
+            let entry = entry
+                .as_ref()
+                .map(|entry| (PRECERT_ENTRY, entry.as_slice()));
+            scts.extend(verify(sct, entry, SctSource::Embedded, log_list, now));
+        }
+    }
+    let entry = x509_entry(leaf);
+    let lists = [
+        (server_hello_scts(received), SctSource::TlsExtension),
+        (ocsp_scts(ocsp_response), SctSource::Ocsp),
+    ];
+    for (list, source) in lists.iter() {
+        for sct in list.iter().flat_map(|list| split_list(list)) {
+            let entry = Some((X509_ENTRY, entry.as_slice()));
+            scts.extend(verify(sct, entry, *source, log_list, now));
+        }
+    }
+    scts
+}
+
+/// Timestamp parsed from TLS encoding and verified, None if it is malformed
+fn verify(
+    sct: &[u8],
+    entry: Option<(u16, &[u8])>,
+    source: SctSource,
+    log_list: &CtLogList,
+    now: i64,
+) -> Option<Sct> {
+    // version, log ID, timestamp, extensions and signature
+    if sct.first() != Some(&0) || sct.len() < 43 {
+        return None;
+    }
+    let log_id = &sct[1..33];
+    let mut timestamp = [0; 8];
+    timestamp.copy_from_slice(&sct[33..41]);
+    let timestamp = u64::from_be_bytes(timestamp);
+    let (extensions, rest) = read_vector(&sct[41..])?;
+    let (hash, algorithm) = (*rest.first()?, *rest.get(1)?);
+    let (signature, _) = read_vector(rest.get(2..)?)?;
+
+    let log = log_list.find(log_id);
+    let valid = match (log, entry) {
+        (Some(log), Some((entry_type, entry))) if timestamp / 1000 <= now as u64 => {
+            let message = signed_data(timestamp, entry_type, entry, extensions);
+            verify_signature(&log.key, hash, algorithm, &message, signature)
+        }
+        _ => false,
+    };
+    Some(Sct {
+        log_id: base64::encode(log_id),
+        log: log.map(|log| log.description.clone()),
+        timestamp: timestamp as i64,
+        source,
+        valid,
+    })
+}
+
+/// Data signed by log, see section 3.2 of RFC 6962
+pub(crate) fn signed_data(
+    timestamp: u64,
+    entry_type: u16,
+    entry: &[u8],
+    extensions: &[u8],
+) -> Vec<u8> {
+    let mut data = vec![0, 0];
+    data.extend_from_slice(&timestamp.to_be_bytes());
+    data.extend_from_slice(&entry_type.to_be_bytes());
+    data.extend_from_slice(entry);
+    data.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+    data.extend_from_slice(extensions);
+    data
+}
+
+/// Entry of certificate, as it is sent in TLS extension or OCSP response
+pub(crate) fn x509_entry(leaf: &[u8]) -> Vec<u8> {
+    [&u24_bytes(leaf.len())[..], leaf].concat()
+}
+
+/// Entry of certificate before timestamps are embedded, with hash of issuer key
+pub(crate) fn precert_entry(leaf: &[u8], issuer: &[u8]) -> Option<Vec<u8>> {
+    let issuer_key_hash = digest::digest(&digest::SHA256, public_key_info(issuer)?);
+    let (_, leaf, _) = expect_der(leaf, TAG_SEQUENCE)?;
+    let (_, mut fields, _) = expect_der(leaf, TAG_SEQUENCE)?;
+    let mut tbs = vec![];
+    while let Some((tag, whole, content, rest)) = read_der(fields) {
+        if tag == TAG_CONTEXT_3 {
+            let (_, mut extensions, _) = expect_der(content, TAG_SEQUENCE)?;
+            let mut kept = vec![];
+            while let Some((_, extension, content, rest)) = read_der(extensions) {
+                if expect_der(content, TAG_OID)?.1 != OID_EMBEDDED_SCTS {
+                    kept.extend_from_slice(extension);
+                }
+                extensions = rest;
+            }
+            tbs.extend(der(tag, &der(TAG_SEQUENCE, &kept)));
+        } else {
+            tbs.extend_from_slice(whole);
+        }
+        fields = rest;
+    }
+    let tbs = der(TAG_SEQUENCE, &tbs);
+    Some([issuer_key_hash.as_ref(), &u24_bytes(tbs.len()), &tbs].concat())
+}
+
+/// SubjectPublicKeyInfo of certificate
+pub(crate) fn public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    let (_, certificate, _) = expect_der(certificate, TAG_SEQUENCE)?;
+    let (_, mut fields, _) = expect_der(certificate, TAG_SEQUENCE)?;
+    if let Some((_, _, rest)) = expect_der(fields, TAG_CONTEXT_0) {
+        fields = rest;
+    }
+    // serial number, signature algorithm, issuer, validity and subject
+    for _ in 0..5 {
+        fields = read_der(fields)?.3;
+    }
+    expect_der(fields, TAG_SEQUENCE).map(|(whole, _, _)| whole)
+}
+
+/// Whether signature of log with key in SubjectPublicKeyInfo format is valid,
+/// only SHA-256 with ECDSA or RSA is allowed by RFC 6962
+fn verify_signature(key: &[u8], hash: u8, algorithm: u8, message: &[u8], sig: &[u8]) -> bool {
+    let parse = || {
+        let (_, key, _) = expect_der(key, TAG_SEQUENCE)?;
+        let (_, key_algorithm, rest) = expect_der(key, TAG_SEQUENCE)?;
+        let (_, oid, _) = expect_der(key_algorithm, TAG_OID)?;
+        let (_, bits, _) = expect_der(rest, TAG_BIT_STRING)?;
+        Some((oid, bits.get(1..)?))
+    };
+    let (oid, bits) = match parse() {
+        Some(parsed) => parsed,
+        None => return false,
+    };
+    let algorithm: &dyn signature::VerificationAlgorithm = match (hash, algorithm, oid) {
+        (4, 3, OID_EC_PUBLIC_KEY) => &signature::ECDSA_P256_SHA256_ASN1,
+        (4, 1, OID_RSA_ENCRYPTION) => &signature::RSA_PKCS1_2048_8192_SHA256,
+        _ => return false,
+    };
+    signature::UnparsedPublicKey::new(algorithm, bits)
+        .verify(message, sig)
+        .is_ok()
+}
+
+/// SCT list in extension of leaf certificate
+fn embedded_scts(leaf: &[u8]) -> Option<Vec<u8>> {
+    let (_, leaf) = x509_parser::parse_x509_certificate(leaf).ok()?;
+    let extension = leaf
+        .extensions()
+        .values()
+        .find(|extension| extension.oid.bytes() == OID_EMBEDDED_SCTS)?;
+    let (_, list, _) = expect_der(extension.value, TAG_OCTET_STRING)?;
+    Some(list.to_vec())
+}
+
+/// SCT list in extension of stapled OCSP response
+fn ocsp_scts(response: &[u8]) -> Option<Vec<u8>> {
+    let (_, response, _) = expect_der(response, TAG_SEQUENCE)?;
+    let (_, _, rest) = expect_der(response, TAG_ENUMERATED)?;
+    let (_, bytes, _) = expect_der(rest, TAG_CONTEXT_0)?;
+    let (_, bytes, _) = expect_der(bytes, TAG_SEQUENCE)?;
+    let (_, _, rest) = expect_der(bytes, TAG_OID)?;
+    let (_, basic, _) = expect_der(rest, TAG_OCTET_STRING)?;
+    let value = find_extension(basic, OID_OCSP_SCTS)?;
+    let (_, list, _) = expect_der(value, TAG_OCTET_STRING)?;
+    Some(list.to_vec())
+}
+
+/// Value of the first extension with OID in nested DER elements, like the
+/// extensions of OCSP response which x509-parser does not parse
+fn find_extension<'a>(input: &'a [u8], oid: &[u8]) -> Option<&'a [u8]> {
+    let mut input = input;
+    while let Some((tag, _, content, rest)) = read_der(input) {
+        if tag == TAG_SEQUENCE {
+            if let Some((_, extn_id, mut value)) = expect_der(content, TAG_OID) {
+                if extn_id == oid {
+                    // optional critical flag
+                    if let Some((_, _, rest)) = expect_der(value, TAG_BOOLEAN) {
+                        value = rest;
+                    }
+                    return expect_der(value, TAG_OCTET_STRING).map(|(_, value, _)| value);
+                }
+            }
+        }
+        if tag & 0x20 != 0 {
+            if let Some(value) = find_extension(content, oid) {
+                return Some(value);
+            }
+        }
+        input = rest;
+    }
+    None
+}
+
+/// SCT list in extension of ServerHello received from server in plaintext
+pub(crate) fn server_hello_scts(received: &[u8]) -> Option<Vec<u8>> {
+    // handshake messages may be fragmented across records, and records after
+    // ServerHello, e.g. long Certificate, may be cut off at recording limit
+    let mut handshake = vec![];
+    let mut records = received;
+    while records.len() >= 5 && records[0] == 0x16 && !is_complete(&handshake) {
+        let len = u16::from_be_bytes([records[3], records[4]]) as usize;
+        let record = match records.get(5..5 + len) {
+            Some(record) => record,
+            None => break,
+        };
+        handshake.extend_from_slice(record);
+        records = &records[5 + len..];
+    }
+    // ServerHello
+    if handshake.first() != Some(&2) || !is_complete(&handshake) {
+        return None;
+    }
+    let len = u32::from_be_bytes([0, handshake[1], handshake[2], handshake[3]]) as usize;
+    let hello = &handshake[4..4 + len];
+    // version and random
+    let rest = hello.get(34..)?;
+    let session_id = *rest.first()? as usize;
+    // cipher suite and compression method
+    let rest = rest.get(1 + session_id + 3..)?;
+    let (mut extensions, _) = read_vector(rest)?;
+    while extensions.len() >= 4 {
+        let kind = u16::from_be_bytes([extensions[0], extensions[1]]);
+        let (data, rest) = read_vector(&extensions[2..])?;
+        if kind == EXTENSION_SCT {
+            return Some(data.to_vec());
+        }
+        extensions = rest;
+    }
+    None
+}
+
+/// Whether the first handshake message is received in whole
+fn is_complete(handshake: &[u8]) -> bool {
+    match handshake.get(1..4) {
+        Some(len) => {
+            let len = u32::from_be_bytes([0, len[0], len[1], len[2]]) as usize;
+            handshake.len() >= 4 + len
+        }
+        None => false,
+    }
+}
+
+/// Each SCT in list with 16-bit length prefixes
+fn split_list(list: &[u8]) -> Vec<&[u8]> {
+    let mut scts = vec![];
+    let mut rest = match read_vector(list) {
+        Some((list, _)) => list,
+        None => return scts,
+    };
+    while let Some((sct, next)) = read_vector(rest) {
+        scts.push(sct);
+        rest = next;
+    }
+    scts
+}
+
+/// Bytes with 16-bit length prefix, and the rest of input
+fn read_vector(input: &[u8]) -> Option<(&[u8], &[u8])> {
+    let len = u16::from_be_bytes([*input.first()?, *input.get(1)?]) as usize;
+    let vector = input.get(2..2 + len)?;
+    Some((vector, &input[2 + len..]))
+}
+
+/// SCT list with 16-bit length prefixes, as it is sent in TLS extension
+#[cfg(test)]
+pub(crate) fn encode_list(scts: &[Vec<u8>]) -> Vec<u8> {
+    let list: Vec<u8> = scts
+        .iter()
+        .flat_map(|sct| [&(sct.len() as u16).to_be_bytes()[..], sct].concat())
+        .collect();
+    [&(list.len() as u16).to_be_bytes()[..], &list].concat()
+}
+
+fn u24_bytes(n: usize) -> [u8; 3] {
+    let bytes = (n as u32).to_be_bytes();
+    [bytes[1], bytes[2], bytes[3]]
+}
+
+/// Stream which records the beginning of what is read from server, so that
+/// ServerHello can be inspected after handshake
+pub(crate) struct RecordingStream<S> {
+    inner: S,
+    received: Vec<u8>,
+    limit: usize,
+}
+
+impl<S> RecordingStream<S> {
+    /// Stream which records when enabled
+    pub(crate) fn new(inner: S, enabled: bool) -> Self {
+        RecordingStream {
+            inner,
+            received: vec![],
+            limit: if enabled { RECORDING_LIMIT } else { 0 },
+        }
+    }
+
+    /// Bytes received from server up to the limit
+    pub(crate) fn received(&self) -> &[u8] {
+        &self.received
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for RecordingStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+        let room = this.limit.saturating_sub(this.received.len());
+        let read = &buf.filled()[filled..];
+        this.received
+            .extend_from_slice(&read[..read.len().min(room)]);
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for RecordingStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+
+    use crate::ct::{
+        embedded_scts, encode_list, scts, server_hello_scts, CtLogList, CtPolicy, SctSource,
+    };
+    use crate::fixtures::{TestLog, TestServer};
+
+    #[test]
+    fn test_log_list() {
+        let log = TestLog::new("hcc test log");
+        let json = TestLog::log_list(&[&log]);
+        let list = CtLogList::from_json(&json).unwrap();
+        assert_eq!(1, list.logs.len());
+        assert_eq!("hcc test log", list.logs[0].description);
+        assert_eq!(log.id().to_vec(), list.logs[0].id);
+
+        assert!(CtLogList::from_json("{}").is_err());
+        let json =
+            r#"{"operators": [{"logs": [{"description": "short", "log_id": "AAAA", "key": ""}]}]}"#;
+        assert!(CtLogList::from_json(json).is_err());
+    }
+
+    #[test]
+    fn test_embedded_scts() {
+        let logs = [TestLog::new("first"), TestLog::new("second")];
+        let server = TestServer::with_embedded_scts(&[&logs[0], &logs[1]]);
+        let leaf = &server.chain()[0].0;
+        assert!(embedded_scts(leaf).is_some());
+
+        let now = Utc::now().timestamp();
+        let log_list = CtLogList::from_json(&TestLog::log_list(&[&logs[0]])).unwrap();
+        let found = scts(server.chain(), &[], &[], &log_list, now);
+        assert_eq!(2, found.len());
+        assert_eq!(SctSource::Embedded, found[0].source);
+        assert!(found[0].valid);
+        assert_eq!(Some("first".to_string()), found[0].log);
+        // log is not in log list
+        assert!(!found[1].valid);
+        assert_eq!(None, found[1].log);
+
+        let policy = CtPolicy {
+            min_logs: 2,
+            log_list,
+        };
+        let violation = policy.violation(&found);
+        assert_eq!(
+            Some("SCTs from 1 of 2 required CT logs".to_string()),
+            violation
+        );
+        let policy = CtPolicy {
+            min_logs: 1,
+            ..policy
+        };
+        assert_eq!(None, policy.violation(&found));
+
+        // timestamps cannot be verified without issuer
+        let log_list = CtLogList::from_json(&TestLog::log_list(&[&logs[0], &logs[1]])).unwrap();
+        let found = scts(&server.chain()[..1], &[], &[], &log_list, now);
+        assert!(found.iter().all(|sct| !sct.valid));
+    }
+
+    #[test]
+    fn test_delivered_scts() {
+        let log = TestLog::new("hcc test log");
+        // OCSP response is signed by intermediate certificate authority
+        let server = TestServer::with_revocation_url("http://127.0.0.1:1");
+        let log_list = CtLogList::from_json(&TestLog::log_list(&[&log])).unwrap();
+        let now = Utc::now().timestamp();
+        let list = encode_list(&[log.sign_x509(&server.chain()[0].0)]);
+
+        // ServerHello with session ID, cipher suite and SCT extension
+        let mut hello = vec![0x03, 0x03];
+        hello.extend_from_slice(&[7; 32]);
+        hello.extend_from_slice(&[1, 9, 0xc0, 0x2b, 0]);
+        let extension = [&[0, 0x12][..], &(list.len() as u16).to_be_bytes(), &list].concat();
+        hello.extend_from_slice(&(extension.len() as u16).to_be_bytes());
+        hello.extend_from_slice(&extension);
+        let len = (hello.len() as u32).to_be_bytes();
+        let message = [&[2, len[1], len[2], len[3]][..], &hello].concat();
+        let len = (message.len() as u16).to_be_bytes();
+        let record = [&[0x16, 0x03, 0x03, len[0], len[1]][..], &message].concat();
+        assert_eq!(Some(list.clone()), server_hello_scts(&record));
+        assert_eq!(None, server_hello_scts(&record[..20]));
+
+        // Certificate record cut off at recording limit after complete ServerHello
+        let certificate = [&[0x16, 0x03, 0x03, 0x40, 0x00, 11][..], &[0; 100]].concat();
+        let received = [record.as_slice(), &certificate].concat();
+        assert_eq!(Some(list.clone()), server_hello_scts(&received));
+
+        // ServerHello fragmented across two records
+        let (first, second) = message.split_at(30);
+        let fragments = [first, second]
+            .iter()
+            .flat_map(|fragment| {
+                let len = (fragment.len() as u16).to_be_bytes();
+                [&[0x16, 0x03, 0x03, len[0], len[1]][..], fragment].concat()
+            })
+            .collect::<Vec<u8>>();
+        let received = [fragments.as_slice(), &certificate].concat();
+        assert_eq!(Some(list.clone()), server_hello_scts(&received));
+        assert_eq!(None, server_hello_scts(&fragments[..40]));
+
+        let found = scts(server.chain(), &record, &[], &log_list, now);
+        assert_eq!(1, found.len());
+        assert_eq!(SctSource::TlsExtension, found[0].source);
+        assert!(found[0].valid);
+
+        // timestamp in the future
+        let found = scts(server.chain(), &record, &[], &log_list, now - 7200);
+        assert!(!found[0].valid);
+
+        let response = server.ocsp_response_with_scts(&list);
+        let found = scts(server.chain(), &[], &response, &log_list, now);
+        assert_eq!(1, found.len());
+        assert_eq!(SctSource::Ocsp, found[0].source);
+        assert!(found[0].valid);
+    }
+}
@@ -0,0 +1,131 @@
+//! Minimal DER reader and writer for structures x509-parser does not cover,
+//! like OCSP responses and certificates rebuilt for Certificate Transparency
+
+pub(crate) const TAG_BOOLEAN: u8 = 0x01;
+pub(crate) const TAG_INTEGER: u8 = 0x02;
+pub(crate) const TAG_BIT_STRING: u8 = 0x03;
+pub(crate) const TAG_OCTET_STRING: u8 = 0x04;
+pub(crate) const TAG_NULL: u8 = 0x05;
+pub(crate) const TAG_OID: u8 = 0x06;
+pub(crate) const TAG_ENUMERATED: u8 = 0x0a;
+#[cfg(test)]
+pub(crate) const TAG_UTC_TIME: u8 = 0x17;
+pub(crate) const TAG_GENERALIZED_TIME: u8 = 0x18;
+pub(crate) const TAG_SEQUENCE: u8 = 0x30;
+/// Context-specific constructed tag [0]
+pub(crate) const TAG_CONTEXT_0: u8 = 0xa0;
+/// Context-specific constructed tag [1]
+pub(crate) const TAG_CONTEXT_1: u8 = 0xa1;
+/// Context-specific constructed tag [3], extensions of certificate
+pub(crate) const TAG_CONTEXT_3: u8 = 0xa3;
+
+/// DER element with tag and content
+pub(crate) fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut element = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        element.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        element.push(0x80 | (bytes.len() - skip) as u8);
+        element.extend_from_slice(&bytes[skip..]);
+    }
+    element.extend_from_slice(content);
+    element
+}
+
+/// Tag, the whole element, its content and the rest of input
+pub(crate) type Element<'a> = (u8, &'a [u8], &'a [u8], &'a [u8]);
+
+/// The first DER element in input, none when it is truncated or its length
+/// is indefinite, longer than 4 bytes or not in the shortest form
+pub(crate) fn read_der(input: &[u8]) -> Option<Element<'_>> {
+    let tag = *input.first()?;
+    let first = *input.get(1)? as usize;
+    let (len, header) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let n = first & 0x7f;
+        if n == 0 || n > 4 {
+            return None;
+        }
+        let bytes = input.get(2..2 + n)?;
+        if bytes[0] == 0 {
+            return None;
+        }
+        let len = bytes.iter().fold(0usize, |len, b| len << 8 | *b as usize);
+        if len < 0x80 {
+            return None;
+        }
+        (len, 2 + n)
+    };
+    let end = header.checked_add(len)?;
+    let content = input.get(header..end)?;
+    Some((tag, &input[..end], content, &input[end..]))
+}
+
+/// The first DER element in input when it has tag, returns the whole element, content and the rest
+pub(crate) fn expect_der(input: &[u8], tag: u8) -> Option<(&[u8], &[u8], &[u8])> {
+    match read_der(input)? {
+        (t, whole, content, rest) if t == tag => Some((whole, content, rest)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::der::{
+        der, expect_der, read_der, TAG_INTEGER, TAG_OCTET_STRING, TAG_OID, TAG_SEQUENCE,
+    };
+
+    #[test]
+    fn test_der() {
+        let short = der(TAG_OCTET_STRING, &[1, 2, 3]);
+        assert_eq!(vec![0x04, 3, 1, 2, 3], short);
+        let long = der(TAG_OCTET_STRING, &[0; 300]);
+        assert_eq!([0x04, 0x82, 0x01, 0x2c], long[..4]);
+
+        let input = [long.as_slice(), &short].concat();
+        let (tag, whole, content, rest) = read_der(&input).unwrap();
+        assert_eq!(TAG_OCTET_STRING, tag);
+        assert_eq!(long, whole);
+        assert_eq!(300, content.len());
+        assert_eq!(short, rest);
+
+        let nested = der(
+            TAG_SEQUENCE,
+            &[der(TAG_OID, &[0x2a]), short.clone()].concat(),
+        );
+        let (_, content, rest) = expect_der(&nested, TAG_SEQUENCE).unwrap();
+        assert!(rest.is_empty());
+        let (_, oid, rest) = expect_der(content, TAG_OID).unwrap();
+        assert_eq!([0x2a], oid);
+        assert_eq!(short, rest);
+        assert!(expect_der(rest, TAG_INTEGER).is_none());
+    }
+
+    #[test]
+    fn test_read_truncated_der() {
+        let long = der(TAG_OCTET_STRING, &[0; 300]);
+        assert!(read_der(&[]).is_none());
+        assert!(read_der(&[TAG_OCTET_STRING]).is_none());
+        // content shorter than length
+        assert!(read_der(&[TAG_OCTET_STRING, 3, 1, 2]).is_none());
+        assert!(read_der(&long[..100]).is_none());
+        // length bytes missing
+        assert!(read_der(&long[..3]).is_none());
+        assert!(read_der(&[TAG_OCTET_STRING, 0x84, 0xff, 0xff]).is_none());
+    }
+
+    #[test]
+    fn test_read_malformed_der() {
+        // indefinite length
+        assert!(read_der(&[TAG_SEQUENCE, 0x80, 0, 0]).is_none());
+        // more length bytes than usize on 32-bit targets
+        assert!(read_der(&[TAG_OCTET_STRING, 0x85, 0, 0, 0, 0, 1, 0]).is_none());
+        // length not in the shortest form
+        assert!(read_der(&[TAG_OCTET_STRING, 0x81, 0x01, 0]).is_none());
+        assert!(read_der(&[TAG_OCTET_STRING, 0x82, 0x00, 0x81, 0]).is_none());
+    }
+}
@@ -0,0 +1,339 @@
+//! Reading certificates from local files
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+use p12::{AlgorithmIdentifier, ContentInfo, EncryptedContentInfo, MacData, SafeBag, PFX};
+use pkcs8::der::{asn1::Any, Decodable};
+use pkcs8::pkcs5::pbes2;
+use ring::{digest, hmac};
+
+use crate::check_result::ErrorKind;
+
+/// Extensions of certificate files picked up when scanning directories
+const EXTENSIONS: &[&str] = &["pem", "crt", "cer", "der", "p12", "pfx"];
+
+/// Expand glob patterns and scan directories recursively for certificate files
+///
+/// ```
+/// # use hcc::find_files;
+/// let paths = find_files(&["/etc/ssl/certs/*.pem"]).unwrap();
+/// ```
+///
+/// A pattern which matches nothing is kept as is, so that it is reported
+/// as a file which cannot be read.
+pub fn find_files(patterns: &[&str]) -> anyhow::Result<Vec<String>> {
+    let mut paths = vec![];
+    for pattern in patterns {
+        let entries =
+            glob::glob(pattern).with_context(|| format!("invalid pattern {0}", pattern))?;
+        let mut matched = false;
+        for entry in entries {
+            let path = entry?;
+            matched = true;
+            if path.is_dir() {
+                scan_dir(&path, &mut paths)?;
+            } else {
+                paths.push(path.display().to_string());
+            }
+        }
+        if !matched {
+            paths.push(pattern.to_string());
+        }
+    }
+    // a file matched by several patterns is checked once, in order of first match
+    let mut seen = HashSet::new();
+    paths.retain(|path| seen.insert(path.clone()));
+    Ok(paths)
+}
+
+/// Collect certificate files in directory and its subdirectories in sorted order
+fn scan_dir(dir: &Path, paths: &mut Vec<String>) -> anyhow::Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .with_context(|| format!("failed to read {0}", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            scan_dir(&path, paths)?;
+        } else if has_extension(&path, EXTENSIONS) {
+            paths.push(path.display().to_string());
+        }
+    }
+    Ok(())
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    matches!(extension, Some(extension)
+        if extensions.iter().any(|e| e.eq_ignore_ascii_case(extension)))
+}
+
+/// Certificates in PEM bundle, DER file or PKCS#12 archive in DER format,
+/// in the order they are stored
+pub(crate) fn read_certificates(path: &Path, password: &str) -> Result<Vec<Vec<u8>>, ErrorKind> {
+    let bytes = fs::read(path).map_err(|_| ErrorKind::File)?;
+    if has_extension(path, &["p12", "pfx"]) {
+        return read_pkcs12(&bytes, password);
+    }
+    if bytes.windows(10).any(|w| w == b"-----BEGIN") {
+        return match rustls::internal::pemfile::certs(&mut bytes.as_slice()) {
+            Ok(certificates) if !certificates.is_empty() => {
+                Ok(certificates.into_iter().map(|c| c.0).collect())
+            }
+            _ => Err(ErrorKind::BadCertificate),
+        };
+    }
+    if x509_parser::parse_x509_certificate(&bytes).is_ok() {
+        return Ok(vec![bytes]);
+    }
+    read_pkcs12(&bytes, password)
+}
+
+/// PBES2 encryption scheme of PKCS#5, used by OpenSSL 3 by default
+const OID_PBES2: &[u64] = &[1, 2, 840, 113549, 1, 5, 13];
+
+/// Digests of MAC with their block length in bytes
+const MAC_DIGESTS: &[(&[u64], &digest::Algorithm, hmac::Algorithm, usize)] = &[
+    (
+        &[2, 16, 840, 1, 101, 3, 4, 2, 1],
+        &digest::SHA256,
+        hmac::HMAC_SHA256,
+        64,
+    ),
+    (
+        &[2, 16, 840, 1, 101, 3, 4, 2, 2],
+        &digest::SHA384,
+        hmac::HMAC_SHA384,
+        128,
+    ),
+    (
+        &[2, 16, 840, 1, 101, 3, 4, 2, 3],
+        &digest::SHA512,
+        hmac::HMAC_SHA512,
+        128,
+    ),
+];
+
+/// Certificates in PKCS#12 archive, encrypted with legacy PBE or PBES2,
+/// and protected by SHA-1 or SHA-2 MAC
+fn read_pkcs12(bytes: &[u8], password: &str) -> Result<Vec<Vec<u8>>, ErrorKind> {
+    let pfx = PFX::parse(bytes).map_err(|_| ErrorKind::BadCertificate)?;
+    // archive signed with public key instead of password is not supported
+    let data = match &pfx.auth_safe {
+        ContentInfo::Data(data) => data,
+        _ => return Err(ErrorKind::UnsupportedPkcs12),
+    };
+    if let Some(mac_data) = &pfx.mac_data {
+        if !verify_mac(mac_data, data, password)? {
+            return Err(ErrorKind::Password);
+        }
+    }
+    let contents = yasna::parse_der(data, |r| r.collect_sequence_of(ContentInfo::parse))
+        .map_err(|_| ErrorKind::BadCertificate)?;
+    let mut certificates = vec![];
+    for content in contents {
+        let data = match content {
+            ContentInfo::Data(data) => data,
+            ContentInfo::EncryptedData(encrypted) => {
+                decrypt(&encrypted.encrypted_content_info, password)?
+            }
+            ContentInfo::OtherContext(_) => return Err(ErrorKind::UnsupportedPkcs12),
+        };
+        let bags = yasna::parse_der(&data, |r| r.collect_sequence_of(SafeBag::parse))
+            .map_err(|_| ErrorKind::BadCertificate)?;
+        certificates.extend(bags.iter().filter_map(|bag| bag.bag.get_x509_cert()));
+    }
+    if certificates.is_empty() {
+        return Err(ErrorKind::BadCertificate);
+    }
+    Ok(certificates)
+}
+
+/// Whether MAC of authenticated safe matches password
+fn verify_mac(mac_data: &MacData, data: &[u8], password: &str) -> Result<bool, ErrorKind> {
+    let password = bmp_string(password);
+    let algorithm = match &mac_data.mac.digest_algorithm {
+        AlgorithmIdentifier::Sha1 => return Ok(mac_data.verify_mac(data, &password)),
+        AlgorithmIdentifier::OtherAlg(algorithm) => algorithm,
+        _ => return Err(ErrorKind::UnsupportedPkcs12),
+    };
+    let (_, digest, hmac, block_len) = MAC_DIGESTS
+        .iter()
+        .find(|(oid, ..)| algorithm.algorithm_type.components().as_slice() == *oid)
+        .ok_or(ErrorKind::UnsupportedPkcs12)?;
+    let key = mac_key(
+        digest,
+        *block_len,
+        &password,
+        &mac_data.salt,
+        mac_data.iterations,
+    );
+    let key = hmac::Key::new(*hmac, &key);
+    Ok(hmac::verify(&key, data, &mac_data.mac.digest).is_ok())
+}
+
+/// MAC key derived from password by appendix B.2 of RFC 7292, where key is
+/// as long as digest so that only the first block is needed
+fn mac_key(
+    algorithm: &'static digest::Algorithm,
+    block_len: usize,
+    password: &[u8],
+    salt: &[u8],
+    iterations: u32,
+) -> Vec<u8> {
+    let fill = |s: &[u8]| -> Vec<u8> {
+        let len = s.len().div_ceil(block_len) * block_len;
+        s.iter().cycle().take(len).copied().collect()
+    };
+    // diversifier 3 is for MAC key
+    let mut a = [vec![3; block_len], fill(salt), fill(password)].concat();
+    for _ in 0..iterations.max(1) {
+        a = digest::digest(algorithm, &a).as_ref().to_vec();
+    }
+    a
+}
+
+/// Content of safe decrypted with PBES2 or legacy PBE of PKCS#12
+fn decrypt(info: &EncryptedContentInfo, password: &str) -> Result<Vec<u8>, ErrorKind> {
+    let ciphertext = &info.encrypted_content;
+    match &info.content_encryption_algorithm {
+        AlgorithmIdentifier::OtherAlg(algorithm)
+            if algorithm.algorithm_type.components().as_slice() == OID_PBES2 =>
+        {
+            let params = algorithm.params.as_deref().unwrap_or_default();
+            let params = Any::from_der(params)
+                .and_then(pbes2::Parameters::try_from)
+                .map_err(|_| ErrorKind::UnsupportedPkcs12)?;
+            params
+                .decrypt(password, ciphertext)
+                .map_err(|_| ErrorKind::Password)
+        }
+        AlgorithmIdentifier::OtherAlg(_) => Err(ErrorKind::UnsupportedPkcs12),
+        algorithm => algorithm
+            .decrypt_pbe(ciphertext, &bmp_string(password))
+            .ok_or(ErrorKind::Password),
+    }
+}
+
+/// Password as BMPString with terminating null, as PKCS#12 expects
+fn bmp_string(s: &str) -> Vec<u8> {
+    s.encode_utf16()
+        .chain(Some(0))
+        .flat_map(|c| c.to_be_bytes().to_vec())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use chrono::Utc;
+    use p12::{AlgorithmIdentifier, OtherAlgorithmIdentifier, PFX};
+    use yasna::models::ObjectIdentifier;
+
+    use crate::certificate::CertificateInfo;
+    use crate::check_result::ErrorKind;
+    use crate::file::{find_files, read_certificates};
+    use crate::fixtures::{temp_dir, TestServer, OPENSSL3_PKCS12};
+
+    #[test]
+    fn test_find_files() {
+        let dir = temp_dir("find_files");
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("a.pem"), "").unwrap();
+        fs::write(dir.join("b.der"), "").unwrap();
+        fs::write(dir.join("README"), "").unwrap();
+        fs::write(dir.join("nested").join("c.p12"), "").unwrap();
+
+        let d = dir.display();
+        let paths = find_files(&[&d.to_string()]).unwrap();
+        let expected = vec![
+            format!("{0}/a.pem", d),
+            format!("{0}/b.der", d),
+            format!("{0}/nested/c.p12", d),
+        ];
+        assert_eq!(expected, paths);
+
+        let paths = find_files(&[&format!("{0}/*.pem", d)]).unwrap();
+        assert_eq!(vec![format!("{0}/a.pem", d)], paths);
+
+        let paths = find_files(&[&format!("{0}/**/*.p12", d)]).unwrap();
+        assert_eq!(vec![format!("{0}/nested/c.p12", d)], paths);
+
+        let a = format!("{0}/a.pem", d);
+        let paths = find_files(&[&a, &format!("{0}/b.der", d), &format!("{0}/*.pem", d)]).unwrap();
+        assert_eq!(vec![a, format!("{0}/b.der", d)], paths);
+
+        let paths = find_files(&["/nonexistent.pem"]).unwrap();
+        assert_eq!(vec!["/nonexistent.pem"], paths);
+
+        assert!(find_files(&["[invalid"]).is_err());
+    }
+
+    #[test]
+    fn test_read_certificates() {
+        let server = TestServer::new();
+        let dir = temp_dir("read_certificates");
+        let der = server.chain()[0].0.clone();
+
+        let path = dir.join("leaf.pem");
+        fs::write(&path, server.chain_pem()).unwrap();
+        assert_eq!(vec![der.clone()], read_certificates(&path, "").unwrap());
+
+        let path = dir.join("leaf.der");
+        fs::write(&path, &der).unwrap();
+        assert_eq!(vec![der.clone()], read_certificates(&path, "").unwrap());
+
+        let path = dir.join("leaf.p12");
+        fs::write(&path, server.pkcs12("secret")).unwrap();
+        assert_eq!(
+            vec![der.clone()],
+            read_certificates(&path, "secret").unwrap()
+        );
+        assert_eq!(Err(ErrorKind::Password), read_certificates(&path, "wrong"));
+
+        // PKCS#12 archive is detected without extension
+        let path = dir.join("leaf.bin");
+        fs::write(&path, server.pkcs12("")).unwrap();
+        assert_eq!(vec![der], read_certificates(&path, "").unwrap());
+
+        let path = dir.join("garbage.pem");
+        fs::write(&path, "not a certificate").unwrap();
+        assert_eq!(Err(ErrorKind::BadCertificate), read_certificates(&path, ""));
+
+        let path = dir.join("nonexistent.pem");
+        assert_eq!(Err(ErrorKind::File), read_certificates(&path, ""));
+    }
+
+    #[test]
+    fn test_read_pkcs12_pbes2() {
+        let dir = temp_dir("read_pkcs12_pbes2");
+        let path = dir.join("openssl3.p12");
+        fs::write(&path, base64::decode(OPENSSL3_PKCS12).unwrap()).unwrap();
+        let certificates = read_certificates(&path, "secret").unwrap();
+        assert_eq!(1, certificates.len());
+        let leaf = CertificateInfo::parse(&certificates[0], &Utc::now()).unwrap();
+        assert_eq!(Some("openssl3.example.com"), leaf.common_name.as_deref());
+        assert_eq!(Err(ErrorKind::Password), read_certificates(&path, "wrong"));
+
+        // MAC with MD5 is neither legacy nor PBES2
+        let server = TestServer::new();
+        let mut pfx = PFX::parse(&server.pkcs12("secret")).unwrap();
+        let md5 = ObjectIdentifier::from_slice(&[1, 2, 840, 113549, 2, 5]);
+        pfx.mac_data.as_mut().unwrap().mac.digest_algorithm =
+            AlgorithmIdentifier::OtherAlg(OtherAlgorithmIdentifier {
+                algorithm_type: md5,
+                params: None,
+            });
+        let path = dir.join("md5.p12");
+        fs::write(&path, pfx.to_der()).unwrap();
+        assert_eq!(
+            Err(ErrorKind::UnsupportedPkcs12),
+            read_certificates(&path, "secret")
+        );
+    }
+}
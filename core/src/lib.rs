@@ -1,7 +1,50 @@
 #![forbid(unsafe_code)]
+pub use certificate::CertificateInfo;
+pub use certificate::CryptoPolicy;
 pub use check_client::CheckClient;
+pub use check_client::CheckClientBuilder;
+pub use check_result::CertificateInfoJSON;
 pub use check_result::CheckResult;
 pub use check_result::CheckResultJSON;
+pub use check_result::CheckState;
+pub use check_result::ErrorKind;
+pub use ct::CtLog;
+pub use ct::CtLogList;
+pub use ct::CtPolicy;
+pub use ct::Sct;
+pub use ct::SctJSON;
+pub use ct::SctSource;
+pub use file::find_files;
+pub use pair::verify_pair;
+pub use pair::PairResult;
+pub use pair::PairResultJSON;
+pub use pin::Pin;
+pub use protocol::Protocol;
+pub use revocation::RevocationStatus;
+pub use scan::ScanPolicy;
+pub use scan::ScanResult;
+pub use scan::ScanResultJSON;
+pub use scan::SuiteScan;
+pub use scan::TlsVersion;
+pub use scan::VersionScan;
+pub use scan::VersionScanJSON;
+pub use target::Target;
+pub use threshold::Threshold;
 
+mod certificate;
 mod check_client;
 mod check_result;
+mod ct;
+mod der;
+mod file;
+#[cfg(test)]
+mod fixtures;
+mod pair;
+mod pin;
+mod protocol;
+mod revocation;
+mod roots;
+mod scan;
+mod target;
+mod threshold;
+mod verifier;
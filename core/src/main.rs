@@ -1,7 +1,16 @@
 #![forbid(unsafe_code)]
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+use anyhow::bail;
 use structopt::StructOpt;
 
-use hcc::{CheckClient, CheckResultJSON};
+use hcc::{
+    find_files, CheckClient, CheckResultJSON, CryptoPolicy, CtLogList, CtPolicy, PairResultJSON,
+    Pin, ScanPolicy, ScanResultJSON, Threshold, TlsVersion,
+};
 
 #[derive(Debug, Default, StructOpt)]
 #[structopt(author, about)]
@@ -16,52 +25,300 @@ struct Opts {
     command: Option<Command>,
 }
 
+// options are parsed once, boxing them is not worth it
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, StructOpt)]
 enum Command {
     /// Check domain name(s) immediately
     #[structopt()]
-    Check {
-        /// Grace period in days
-        #[structopt(short, long = "grace", default_value = "7")]
-        grace_in_days: i64,
-        /// One or many domain names to check
-        #[structopt()]
-        domain_names: Vec<String>,
-    },
+    Check(CheckOpts),
+    /// Scan protocol versions and cipher suites accepted by domain name(s) against policy
+    #[structopt()]
+    Scan(ScanOpts),
+    /// Verify that private key matches certificate, and that target serves the certificate
+    #[structopt()]
+    VerifyPair(VerifyPairOpts),
+}
+
+#[derive(Debug, StructOpt)]
+struct ScanOpts {
+    /// Oldest protocol version allowed e.g. 1.2
+    #[structopt(long, default_value = "1.2")]
+    min_version: TlsVersion,
+    /// Allow cipher suites in CBC mode
+    #[structopt(long)]
+    allow_cbc: bool,
+    /// Maximum number of domain names scanned at the same time
+    #[structopt(short = "n", long, default_value = "16")]
+    concurrency: usize,
+    /// Timeout in seconds to establish TCP connection
+    #[structopt(long, default_value = "10")]
+    connect_timeout: u64,
+    /// Timeout in seconds for STARTTLS dialog and TLS handshake
+    #[structopt(long, default_value = "10")]
+    handshake_timeout: u64,
+    /// Connect to IP address instead of resolving domain name e.g. example.com:203.0.113.7
+    #[structopt(long, number_of_values = 1, parse(try_from_str = parse_resolve))]
+    resolve: Vec<(String, IpAddr)>,
+    /// One or many domain names to scan, with optional protocol and port
+    #[structopt()]
+    domain_names: Vec<String>,
+}
+
+#[derive(Debug, StructOpt)]
+struct VerifyPairOpts {
+    /// Password of encrypted private key or PKCS#12 archive
+    #[structopt(long, env = "KEY_PASSWORD", hide_env_values = true)]
+    password: Option<String>,
+    /// Domain name which should serve the certificate, with optional protocol and port
+    #[structopt(long)]
+    target: Option<String>,
+    /// Timeout in seconds to check target
+    #[structopt(short, long, default_value = "30")]
+    timeout: u64,
+    /// Certificate in PEM, DER or PKCS#12 format
+    #[structopt()]
+    certificate: PathBuf,
+    /// RSA, ECDSA or Ed25519 private key in PEM or DER format
+    #[structopt()]
+    key: PathBuf,
+}
+
+#[derive(Debug, StructOpt)]
+struct CheckOpts {
+    /// Grace period in days, warn when certificate expires within it
+    #[structopt(short, long = "grace", visible_alias = "warning", default_value = "7")]
+    grace_in_days: i64,
+    /// Report certificate as critical when it expires in this many days or fewer
+    #[structopt(long = "critical")]
+    critical_in_days: Option<i64>,
+    /// Also warn when fraction of certificate lifetime has elapsed, e.g. 2/3, 67% or 0.67
+    #[structopt(long, parse(try_from_str = parse_lifetime_fraction))]
+    lifetime_fraction: Option<f64>,
+    /// Warn by rules for host:port, or host on every port, instead of --grace
+    /// and --lifetime-fraction, e.g. example.com=14d,2/3 or example.com:8443=30d
+    #[structopt(long, number_of_values = 1, parse(try_from_str = parse_threshold))]
+    threshold: Vec<(String, Threshold)>,
+    /// Maximum number of domain names checked at the same time
+    #[structopt(short = "n", long, default_value = "16")]
+    concurrency: usize,
+    /// Timeout in seconds to check one domain name
+    #[structopt(short, long, default_value = "30")]
+    timeout: u64,
+    /// Timeout in seconds to establish TCP connection
+    #[structopt(long, default_value = "10")]
+    connect_timeout: u64,
+    /// Timeout in seconds for STARTTLS dialog and TLS handshake
+    #[structopt(long, default_value = "10")]
+    handshake_timeout: u64,
+    /// Maximum number of attempts when connection fails or times out
+    #[structopt(short, long, default_value = "1")]
+    attempts: u32,
+    /// Delay in milliseconds before the first retry, doubled after each retry
+    #[structopt(long, default_value = "1000")]
+    backoff: u64,
+    /// Check every IPv4 and IPv6 address of each domain name
+    #[structopt(short = "A", long)]
+    all_addresses: bool,
+    /// Tolerance in seconds of certificate not valid yet because of clock skew
+    #[structopt(long, default_value = "0")]
+    clock_skew: u64,
+    /// Connect to IP address instead of resolving domain name e.g. example.com:203.0.113.7
+    #[structopt(long, number_of_values = 1, parse(try_from_str = parse_resolve))]
+    resolve: Vec<(String, IpAddr)>,
+    /// Expect certificate or public key served for host:port, or host on every port, to match
+    /// SHA-256 fingerprint, e.g. example.com=sha256/<base64 of SPKI hash>
+    /// or example.com:8443=<hex of certificate hash>
+    #[structopt(long, number_of_values = 1, parse(try_from_str = parse_pin))]
+    pin: Vec<(String, Pin)>,
+    /// PEM bundle of extra certificate authorities to trust
+    #[structopt(long, number_of_values = 1)]
+    ca_file: Vec<PathBuf>,
+    /// Directory of PEM files of extra certificate authorities to trust
+    #[structopt(long, number_of_values = 1)]
+    ca_dir: Vec<PathBuf>,
+    /// Trust certificate authorities of operating system
+    #[structopt(long)]
+    native_roots: bool,
+    /// Do not trust certificate authorities bundled with hcc
+    #[structopt(long)]
+    no_webpki_roots: bool,
+    /// Report dates of certificates which are not trusted, e.g. self-signed certificates
+    #[structopt(long)]
+    expiry_only: bool,
+    /// Warn about weak keys and signatures, e.g. RSA keys shorter than 2048 bits or SHA-1 signatures
+    #[structopt(long)]
+    weak_crypto: bool,
+    /// Minimum size of RSA keys in bits with --weak-crypto
+    #[structopt(long, default_value = "2048")]
+    min_rsa_bits: u32,
+    /// Accept SHA-1 signatures with --weak-crypto
+    #[structopt(long)]
+    allow_sha1: bool,
+    /// Protocols offered with ALPN in order of preference e.g. h2,http/1.1
+    #[structopt(long, use_delimiter = true)]
+    alpn: Vec<String>,
+    /// Verify signed certificate timestamps with Certificate Transparency logs in log list file
+    /// in JSON format of Chrome, e.g. log_list.json
+    #[structopt(long)]
+    ct_log_list: Option<PathBuf>,
+    /// Minimum number of distinct logs which must sign timestamps with --ct-log-list
+    #[structopt(long, default_value = "2")]
+    min_ct_logs: usize,
+    /// Query OCSP responder and CRL distribution point when server does not staple OCSP response
+    #[structopt(long)]
+    query_revocation: bool,
+    /// Check local certificate files instead, arguments are files, glob patterns or directories
+    #[structopt(short, long)]
+    file: bool,
+    /// Password of PKCS#12 archives
+    #[structopt(long, env = "PKCS12_PASSWORD", hide_env_values = true)]
+    password: Option<String>,
+    /// One or many domain names to check, with optional protocol and port
+    /// e.g. example.com:8443, [::1]:443, smtp://mail.example.com:587 or example.com@203.0.113.7
+    #[structopt()]
+    domain_names: Vec<String>,
 }
 
-fn main() -> anyhow::Result<()> {
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
     let opts: Opts = Opts::from_args();
     match opts.command {
-        Some(Command::Check {
-            ref domain_names,
-            grace_in_days,
-        }) => {
-            let domain_names: Vec<&str> = domain_names.iter().map(AsRef::as_ref).collect();
-            check_command(&opts, &domain_names, grace_in_days)
+        Some(Command::Check(ref check_opts)) => check_command(&opts, check_opts).await,
+        Some(Command::Scan(ref scan_opts)) => {
+            if !scan_command(&opts, scan_opts).await? {
+                process::exit(1);
+            }
+            Ok(())
+        }
+        Some(Command::VerifyPair(ref verify_pair_opts)) => {
+            if !verify_pair_command(&opts, verify_pair_opts).await? {
+                process::exit(1);
+            }
+            Ok(())
         }
         None => Ok(()),
     }
 }
 
-fn check_command<'a>(
-    opts: &Opts,
-    domain_names: &'a [&str],
-    grace_in_days: i64,
-) -> anyhow::Result<()> {
-    let client = CheckClient::builder()
+fn parse_resolve(s: &str) -> anyhow::Result<(String, IpAddr)> {
+    let (domain_name, address) = match s.find(':') {
+        Some(i) => (&s[..i], &s[i + 1..]),
+        None => bail!(
+            "expected domain name and IP address separated by colon in {0}",
+            s
+        ),
+    };
+    let address = address.trim_start_matches('[').trim_end_matches(']');
+    Ok((domain_name.to_string(), address.parse()?))
+}
+
+fn parse_pin(s: &str) -> anyhow::Result<(String, Pin)> {
+    match s.find('=') {
+        Some(i) => Ok((s[..i].to_string(), s[i + 1..].parse()?)),
+        None => bail!("expected target and pin separated by = in {0}", s),
+    }
+}
+
+fn parse_lifetime_fraction(s: &str) -> anyhow::Result<f64> {
+    match s.parse()? {
+        Threshold {
+            days: None,
+            lifetime_fraction: Some(fraction),
+        } => Ok(fraction),
+        _ => bail!(
+            "expected fraction of lifetime like 2/3, 67% or 0.67 in {0}",
+            s
+        ),
+    }
+}
+
+fn parse_threshold(s: &str) -> anyhow::Result<(String, Threshold)> {
+    match s.find('=') {
+        Some(i) => Ok((s[..i].to_string(), s[i + 1..].parse()?)),
+        None => bail!("expected target and threshold separated by = in {0}", s),
+    }
+}
+
+async fn check_command(opts: &Opts, check_opts: &CheckOpts) -> anyhow::Result<()> {
+    let mut builder = CheckClient::builder();
+    for path in check_opts.ca_file.iter() {
+        builder.ca_file(path)?;
+    }
+    for path in check_opts.ca_dir.iter() {
+        builder.ca_dir(path)?;
+    }
+    if check_opts.native_roots {
+        builder.native_roots()?;
+    }
+    builder.webpki_roots(!check_opts.no_webpki_roots);
+    if check_opts.weak_crypto {
+        builder.crypto_policy(CryptoPolicy {
+            min_rsa_bits: check_opts.min_rsa_bits,
+            allow_sha1: check_opts.allow_sha1,
+            ..Default::default()
+        });
+    }
+    if let Some(ref path) = check_opts.ct_log_list {
+        builder.ct_policy(CtPolicy {
+            min_logs: check_opts.min_ct_logs,
+            log_list: CtLogList::from_file(path)?,
+        });
+    }
+    if let Some(ref password) = check_opts.password {
+        builder.pkcs12_password(password);
+    }
+    for (domain_name, address) in check_opts.resolve.iter() {
+        builder.resolve(domain_name, *address);
+    }
+    for (domain_name, pin) in check_opts.pin.iter() {
+        builder.pin(domain_name, pin.clone());
+    }
+    if let Some(days) = check_opts.critical_in_days {
+        builder.critical_in_days(days);
+    }
+    if let Some(fraction) = check_opts.lifetime_fraction {
+        builder.lifetime_fraction(fraction);
+    }
+    for (domain_name, threshold) in check_opts.threshold.iter() {
+        builder.threshold(domain_name, *threshold);
+    }
+    let alpn: Vec<&str> = check_opts.alpn.iter().map(AsRef::as_ref).collect();
+    let client = builder
+        .alpn_protocols(&alpn)
+        .all_addresses(check_opts.all_addresses)
+        .attempts(check_opts.attempts)
+        .backoff(Duration::from_millis(check_opts.backoff))
+        .clock_skew(Duration::from_secs(check_opts.clock_skew))
+        .concurrency(check_opts.concurrency)
+        .connect_timeout(Duration::from_secs(check_opts.connect_timeout))
         .elapsed(opts.verbose)
-        .grace_in_days(grace_in_days)
+        .expiry_only(check_opts.expiry_only)
+        .grace_in_days(check_opts.grace_in_days)
+        .handshake_timeout(Duration::from_secs(check_opts.handshake_timeout))
+        .query_revocation(check_opts.query_revocation)
+        .timeout(Duration::from_secs(check_opts.timeout))
         .build();
 
-    let results = client.check_certificates(domain_names)?;
+    let domain_names: Vec<&str> = check_opts.domain_names.iter().map(AsRef::as_ref).collect();
+    let paths = if check_opts.file {
+        find_files(&domain_names)?
+    } else {
+        vec![]
+    };
+    let paths: Vec<&str> = paths.iter().map(AsRef::as_ref).collect();
+    let results = if check_opts.file {
+        client.check_files(&paths)
+    } else {
+        client.check_certificates(&domain_names).await
+    };
 
     if opts.json {
-        let s = if results.len() > 1 {
+        let s = if results.len() != 1 {
             let json: Vec<CheckResultJSON> = results.iter().map(CheckResultJSON::new).collect();
             serde_json::to_string(&json)?
         } else {
-            let result = results.get(0).unwrap();
+            let result = results.first().unwrap();
             let json = CheckResultJSON::new(result);
             serde_json::to_string(&json)?
         };
@@ -75,9 +332,77 @@ fn check_command<'a>(
     Ok(())
 }
 
+/// Print accepted protocol versions and cipher suites,
+/// returns false if any domain name does not comply with policy
+async fn scan_command(opts: &Opts, scan_opts: &ScanOpts) -> anyhow::Result<bool> {
+    let mut builder = CheckClient::builder();
+    for (domain_name, address) in scan_opts.resolve.iter() {
+        builder.resolve(domain_name, *address);
+    }
+    let client = builder
+        .concurrency(scan_opts.concurrency)
+        .connect_timeout(Duration::from_secs(scan_opts.connect_timeout))
+        .handshake_timeout(Duration::from_secs(scan_opts.handshake_timeout))
+        .scan_policy(ScanPolicy {
+            min_version: scan_opts.min_version,
+            allow_cbc: scan_opts.allow_cbc,
+        })
+        .build();
+
+    let domain_names: Vec<&str> = scan_opts.domain_names.iter().map(AsRef::as_ref).collect();
+    let results = client.scan_targets(&domain_names).await;
+
+    if opts.json {
+        let s = if results.len() != 1 {
+            let json: Vec<ScanResultJSON> = results.iter().map(ScanResultJSON::new).collect();
+            serde_json::to_string(&json)?
+        } else {
+            serde_json::to_string(&ScanResultJSON::new(&results[0]))?
+        };
+        println!("{0}", s);
+    } else {
+        for r in results.iter() {
+            println!("{0}", r);
+        }
+    }
+    Ok(results.iter().all(|r| r.is_compliant()))
+}
+
+/// Print whether private key matches certificate, returns false if it does not
+async fn verify_pair_command(
+    opts: &Opts,
+    verify_pair_opts: &VerifyPairOpts,
+) -> anyhow::Result<bool> {
+    let client = CheckClient::builder()
+        .elapsed(opts.verbose)
+        .timeout(Duration::from_secs(verify_pair_opts.timeout))
+        .build();
+    let password = verify_pair_opts.password.as_deref().unwrap_or_default();
+    let result = client
+        .verify_pair(
+            &verify_pair_opts.certificate,
+            &verify_pair_opts.key,
+            password,
+            verify_pair_opts.target.as_deref(),
+        )
+        .await?;
+
+    if opts.json {
+        println!("{0}", serde_json::to_string(&PairResultJSON::new(&result))?);
+    } else {
+        println!("{0}", result);
+    }
+    Ok(result.is_ok())
+}
+
 #[cfg(test)]
 mod test {
-    use crate::{check_command, Opts};
+    use structopt::StructOpt;
+
+    use crate::{
+        check_command, parse_lifetime_fraction, parse_pin, parse_resolve, parse_threshold,
+        scan_command, verify_pair_command, CheckOpts, Opts, ScanOpts, VerifyPairOpts,
+    };
 
     fn build_opts(json: bool) -> Opts {
         Opts {
@@ -86,27 +411,143 @@ mod test {
         }
     }
 
+    fn build_check_opts(args: &[&str]) -> CheckOpts {
+        CheckOpts::from_iter(std::iter::once("check").chain(args.iter().copied()))
+    }
+
     #[test]
-    fn test_check_command() {
-        let opts = build_opts(false);
-        check_command(&opts, &vec!["sha512.badssl.com"], 7).unwrap();
+    fn test_parse_resolve() {
+        let (domain_name, address) = parse_resolve("example.com:203.0.113.7").unwrap();
+        assert_eq!("example.com", domain_name);
+        assert_eq!("203.0.113.7", address.to_string());
+
+        let (_, address) = parse_resolve("example.com:[2001:db8::1]").unwrap();
+        assert_eq!("2001:db8::1", address.to_string());
+
+        assert!(parse_resolve("example.com").is_err());
+        assert!(parse_resolve("example.com:example.net").is_err());
+
+        let check_opts = build_check_opts(&["--resolve", "example.com:::1", "example.com"]);
+        assert_eq!(1, check_opts.resolve.len());
+        assert_eq!(1, check_opts.domain_names.len());
     }
 
     #[test]
-    fn test_check_command_json() {
-        let opts = build_opts(true);
-        check_command(&opts, &vec!["sha512.badssl.com"], 7).unwrap();
+    fn test_parse_alpn() {
+        let check_opts = build_check_opts(&["--alpn", "h2,http/1.1", "example.com"]);
+        assert_eq!(vec!["h2", "http/1.1"], check_opts.alpn);
+        assert_eq!(1, check_opts.domain_names.len());
     }
 
     #[test]
-    fn test_check_command_expired() {
-        let opts = build_opts(false);
-        check_command(&opts, &vec!["expired.badssl.com"], 7).unwrap();
+    fn test_parse_pin() {
+        let pin = "sha256/47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=";
+        let check_opts = build_check_opts(&["--pin", &format!("example.com={0}", pin)]);
+        assert_eq!(1, check_opts.pin.len());
+        assert_eq!("example.com", check_opts.pin[0].0);
+        assert_eq!(pin, check_opts.pin[0].1.to_string());
+        let (target, _) = parse_pin(&format!("[2001:db8::1]:8443={0}", pin)).unwrap();
+        assert_eq!("[2001:db8::1]:8443", target);
+        assert!(parse_pin("example.com").is_err());
+        assert!(parse_pin("example.com=sha256/AAAA").is_err());
+    }
+
+    #[test]
+    fn test_warning_and_critical() {
+        let check_opts = build_check_opts(&["--warning", "30", "--critical", "7"]);
+        assert_eq!(30, check_opts.grace_in_days);
+        assert_eq!(Some(7), check_opts.critical_in_days);
+        let check_opts = build_check_opts(&["--grace", "14"]);
+        assert_eq!(14, check_opts.grace_in_days);
+        assert_eq!(None, check_opts.critical_in_days);
     }
 
     #[test]
-    fn test_check_command_expired_json() {
+    fn test_parse_threshold() {
+        let check_opts = build_check_opts(&[
+            "--lifetime-fraction",
+            "2/3",
+            "--threshold",
+            "example.com=14d,50%",
+        ]);
+        assert_eq!(Some(2.0 / 3.0), check_opts.lifetime_fraction);
+        let (domain_name, threshold) = &check_opts.threshold[0];
+        assert_eq!("example.com", domain_name);
+        assert_eq!(Some(14), threshold.days);
+        assert_eq!(Some(0.5), threshold.lifetime_fraction);
+        assert!(parse_lifetime_fraction("14d").is_err());
+        assert!(parse_lifetime_fraction("1.5").is_err());
+        let (target, _) = parse_threshold("smtp.example.com:587=30d").unwrap();
+        assert_eq!("smtp.example.com:587", target);
+        assert!(parse_threshold("14d").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_check_command_ca_file() {
+        let opts = build_opts(false);
+        let check_opts = build_check_opts(&["--ca-file", "/nonexistent.pem", "example.com"]);
+        assert!(check_command(&opts, &check_opts).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_check_command_ct_log_list() {
+        let opts = build_opts(false);
+        let check_opts = build_check_opts(&["--ct-log-list", "/nonexistent.json", "example.com"]);
+        assert_eq!(2, check_opts.min_ct_logs);
+        assert!(check_command(&opts, &check_opts).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_check_command_file() {
+        let opts = build_opts(true);
+        // empty file and pattern which matches nothing are reported, not fatal
+        let check_opts = build_check_opts(&["--file", "/dev/null", "/nonexistent/*.pem"]);
+        check_command(&opts, &check_opts).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_scan_command() {
+        let opts = build_opts(true);
+        let scan_opts = ScanOpts::from_iter(&["scan", "--min-version", "1.3", "example.com:-1"]);
+        assert_eq!(hcc::TlsVersion::Tls13, scan_opts.min_version);
+        assert!(!scan_command(&opts, &scan_opts).await.unwrap());
+
+        assert!(ScanOpts::from_iter_safe(&["scan", "--min-version", "2.0"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify_pair_command() {
+        let opts = build_opts(false);
+        let verify_pair_opts =
+            VerifyPairOpts::from_iter(&["verify-pair", "/nonexistent.pem", "/nonexistent.key"]);
+        assert!(verify_pair_command(&opts, &verify_pair_opts).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_check_command() {
+        let opts = build_opts(false);
+        let check_opts = build_check_opts(&["sha512.badssl.com"]);
+        check_command(&opts, &check_opts).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_command_json() {
+        let opts = build_opts(true);
+        let check_opts = build_check_opts(&["sha512.badssl.com"]);
+        check_command(&opts, &check_opts).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_command_expired() {
+        let opts = build_opts(false);
+        let check_opts = build_check_opts(&["expired.badssl.com"]);
+        check_command(&opts, &check_opts).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_command_expired_json() {
         let opts = build_opts(true);
-        check_command(&opts, &vec!["expired.badssl.com"], 7).unwrap();
+        let check_opts = build_check_opts(&["expired.badssl.com"]);
+        check_command(&opts, &check_opts).await.unwrap();
     }
 }
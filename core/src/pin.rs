@@ -0,0 +1,151 @@
+//! Pins of certificates and public keys by SHA-256 fingerprint
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::bail;
+use ring::digest;
+
+use crate::certificate::CertificateInfo;
+use crate::ct;
+
+/// Expected SHA-256 fingerprint of a certificate or its public key in chain
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pin {
+    /// Fingerprint of SubjectPublicKeyInfo in base64, which survives renewal with the same key
+    Spki(String),
+    /// Fingerprint of certificate in hexadecimal separated by colon
+    Certificate(String),
+}
+
+impl Pin {
+    /// Whether certificate or its public key has the fingerprint
+    pub(crate) fn matches(&self, certificate: &CertificateInfo) -> bool {
+        match self {
+            Pin::Spki(fingerprint) => *fingerprint == certificate.spki_fingerprint,
+            Pin::Certificate(fingerprint) => *fingerprint == certificate.fingerprint,
+        }
+    }
+}
+
+impl FromStr for Pin {
+    type Err = anyhow::Error;
+
+    /// Parse pin of public key like `sha256/<base64>` as in HPKP and curl,
+    /// or pin of certificate in hexadecimal with optional colons
+    ///
+    /// ```
+    /// # use hcc::Pin;
+    /// let pin: Pin = "sha256/47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=".parse().unwrap();
+    /// assert!(matches!(pin, Pin::Spki(_)));
+    ///
+    /// let hex = "E3:B0:C4:42:98:FC:1C:14:9A:FB:F4:C8:99:6F:B9:24:27:AE:41:E4:64:9B:93:4C:A4:95:99:1B:78:52:B8:55";
+    /// let pin: Pin = hex.parse().unwrap();
+    /// assert_eq!(hex.to_lowercase(), pin.to_string());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(encoded) = s.strip_prefix("sha256/") {
+            let encoded = encoded.trim_start_matches('/');
+            match base64::decode(encoded) {
+                Ok(fingerprint) if fingerprint.len() == 32 => {
+                    return Ok(Pin::Spki(base64::encode(fingerprint)))
+                }
+                _ => bail!("expected SHA-256 fingerprint in base64 in {0}", s),
+            }
+        }
+        let hex: String = s.chars().filter(|c| *c != ':').collect();
+        if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!(
+                "expected sha256/<base64> or SHA-256 fingerprint in hexadecimal in {0}",
+                s
+            );
+        }
+        let bytes: Vec<u8> = (0..32)
+            .map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap())
+            .collect();
+        Ok(Pin::Certificate(hex_fingerprint(&bytes)))
+    }
+}
+
+impl fmt::Display for Pin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pin::Spki(fingerprint) => write!(f, "sha256/{0}", fingerprint),
+            Pin::Certificate(fingerprint) => write!(f, "{0}", fingerprint),
+        }
+    }
+}
+
+/// SHA-256 fingerprint of certificate in hexadecimal separated by colon
+pub(crate) fn certificate_fingerprint(der: &[u8]) -> String {
+    hex_fingerprint(digest::digest(&digest::SHA256, der).as_ref())
+}
+
+/// SHA-256 fingerprint of SubjectPublicKeyInfo of certificate in base64
+pub(crate) fn spki_fingerprint(der: &[u8]) -> Option<String> {
+    let spki = ct::public_key_info(der)?;
+    Some(base64::encode(digest::digest(&digest::SHA256, spki)))
+}
+
+fn hex_fingerprint(bytes: &[u8]) -> String {
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    hex.join(":")
+}
+
+/// Whether any pin matches any certificate in chain, true when there is no pin
+pub(crate) fn matches_any(pins: &[Pin], chain: &[CertificateInfo]) -> bool {
+    pins.is_empty()
+        || pins
+            .iter()
+            .any(|pin| chain.iter().any(|certificate| pin.matches(certificate)))
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+
+    use crate::certificate::CertificateInfo;
+    use crate::fixtures::TestServer;
+    use crate::pin::{matches_any, Pin};
+
+    #[test]
+    fn test_parse_pin() {
+        let pin: Pin = "sha256//47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+            .parse()
+            .unwrap();
+        assert_eq!(
+            "sha256/47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=",
+            pin.to_string()
+        );
+        let pin: Pin = "E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855"
+            .parse()
+            .unwrap();
+        assert!(pin.to_string().starts_with("e3:b0:c4"));
+
+        assert!("sha256/AAAA".parse::<Pin>().is_err());
+        assert!("e3:b0".parse::<Pin>().is_err());
+        assert!("example.com".parse::<Pin>().is_err());
+    }
+
+    #[test]
+    fn test_matches_any() {
+        let server = TestServer::with_intermediate(Utc::now(), Utc::now());
+        let chain: Vec<CertificateInfo> = server
+            .chain()
+            .iter()
+            .map(|c| CertificateInfo::parse(&c.0, &Utc::now()).unwrap())
+            .collect();
+        assert_eq!(95, chain[0].fingerprint.len());
+        assert_eq!(44, chain[0].spki_fingerprint.len());
+
+        let leaf = Pin::Certificate(chain[0].fingerprint.clone());
+        let intermediate_key = Pin::Spki(chain[1].spki_fingerprint.clone());
+        let other: Pin = "sha256/47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+            .parse()
+            .unwrap();
+        assert!(matches_any(&[], &chain));
+        assert!(matches_any(&[leaf], &chain));
+        assert!(matches_any(&[other.clone(), intermediate_key], &chain));
+        assert!(!matches_any(&[other], &chain));
+    }
+}
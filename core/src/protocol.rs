@@ -0,0 +1,515 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+
+/// Protocol spoken before TLS handshake
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Protocol {
+    /// Implicit TLS e.g. HTTPS
+    #[default]
+    Https,
+    /// SMTP with STARTTLS
+    Smtp,
+    /// IMAP with STARTTLS
+    Imap,
+    /// POP3 with STLS
+    Pop3,
+    /// FTP with AUTH TLS
+    Ftp,
+    /// XMPP client-to-server with STARTTLS
+    Xmpp,
+    /// PostgreSQL with SSLRequest
+    Postgres,
+    /// MySQL with SSL capability flag
+    Mysql,
+    /// LDAP with StartTLS extended operation
+    Ldap,
+    /// Local certificate file, no connection is made
+    File,
+}
+
+/// SSLRequest code of PostgreSQL frontend/backend protocol
+const POSTGRES_SSL_REQUEST: [u8; 8] = [0, 0, 0, 8, 0x04, 0xd2, 0x16, 0x2f];
+
+/// CLIENT_SSL capability flag of MySQL client/server protocol
+const MYSQL_CLIENT_SSL: u16 = 0x0800;
+
+/// CLIENT_PROTOCOL_41 | CLIENT_SSL | CLIENT_SECURE_CONNECTION
+const MYSQL_SSL_REQUEST_CAPABILITIES: u32 = 0x0200 | 0x0800 | 0x8000;
+
+/// Largest line, packet or message accepted before TLS handshake, replies to STARTTLS
+/// dialogs are a few KiB at most
+const MAX_MESSAGE_BYTES: usize = 64 * 1024;
+
+/// OID of LDAP StartTLS extended operation
+const LDAP_START_TLS_OID: &[u8] = b"1.3.6.1.4.1.1466.20037";
+
+impl Protocol {
+    /// Default port of protocol
+    ///
+    /// ```
+    /// # use hcc::Protocol;
+    /// assert_eq!(443, Protocol::Https.default_port());
+    /// assert_eq!(25, Protocol::Smtp.default_port());
+    /// ```
+    pub fn default_port(&self) -> u16 {
+        match self {
+            Protocol::Https => 443,
+            Protocol::Smtp => 25,
+            Protocol::Imap => 143,
+            Protocol::Pop3 => 110,
+            Protocol::Ftp => 21,
+            Protocol::Xmpp => 5222,
+            Protocol::Postgres => 5432,
+            Protocol::Mysql => 3306,
+            Protocol::Ldap => 389,
+            Protocol::File => 0,
+        }
+    }
+
+    /// Upgrade plaintext connection so that TLS handshake can be performed on it
+    pub(crate) async fn starttls<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        domain_name: &str,
+        stream: &mut S,
+    ) -> anyhow::Result<()> {
+        let mut reader = BufReader::new(stream);
+        match self {
+            Protocol::Https | Protocol::File => Ok(()),
+            Protocol::Smtp => {
+                expect_reply(&mut reader, "220").await?;
+                send(&mut reader, "EHLO localhost\r\n").await?;
+                expect_reply(&mut reader, "250").await?;
+                send(&mut reader, "STARTTLS\r\n").await?;
+                expect_reply(&mut reader, "220").await
+            }
+            Protocol::Imap => {
+                let line = read_line(&mut reader).await?;
+                if !line.starts_with("* OK") {
+                    bail!("unexpected IMAP greeting: {0}", line.trim_end());
+                }
+                send(&mut reader, "a001 STARTTLS\r\n").await?;
+                loop {
+                    let line = read_line(&mut reader).await?;
+                    if line.starts_with("a001 OK") {
+                        return Ok(());
+                    } else if line.starts_with("a001 ") {
+                        bail!("IMAP server refused STARTTLS: {0}", line.trim_end());
+                    }
+                }
+            }
+            Protocol::Pop3 => {
+                let line = read_line(&mut reader).await?;
+                if !line.starts_with("+OK") {
+                    bail!("unexpected POP3 greeting: {0}", line.trim_end());
+                }
+                send(&mut reader, "STLS\r\n").await?;
+                let line = read_line(&mut reader).await?;
+                if !line.starts_with("+OK") {
+                    bail!("POP3 server refused STLS: {0}", line.trim_end());
+                }
+                Ok(())
+            }
+            Protocol::Ftp => {
+                expect_reply(&mut reader, "220").await?;
+                send(&mut reader, "AUTH TLS\r\n").await?;
+                expect_reply(&mut reader, "234").await
+            }
+            Protocol::Xmpp => {
+                send(&mut reader, &xmpp_stream_header(domain_name)).await?;
+                let features = read_xml_until(&mut reader, "</stream:features>").await?;
+                if !features.contains("<starttls") {
+                    bail!("XMPP server does not offer STARTTLS");
+                }
+                send(
+                    &mut reader,
+                    "<starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>",
+                )
+                .await?;
+                let tag = read_xml_until(&mut reader, "urn:ietf:params:xml:ns:xmpp-tls").await?;
+                if !tag.contains("<proceed") {
+                    bail!("XMPP server refused STARTTLS");
+                }
+                Ok(())
+            }
+            Protocol::Postgres => {
+                send_bytes(&mut reader, &POSTGRES_SSL_REQUEST).await?;
+                let mut buf = [0; 1];
+                reader.read_exact(&mut buf).await?;
+                match buf[0] {
+                    b'S' => Ok(()),
+                    b'N' => bail!("PostgreSQL server does not support SSL"),
+                    b => bail!("unexpected PostgreSQL response to SSLRequest: {0:#04x}", b),
+                }
+            }
+            Protocol::Mysql => {
+                let (sequence_id, payload) = read_mysql_packet(&mut reader).await?;
+                let capabilities = parse_mysql_capabilities(&payload)?;
+                if capabilities & MYSQL_CLIENT_SSL == 0 {
+                    bail!("MySQL server does not support SSL");
+                }
+                let request = build_mysql_ssl_request(sequence_id.wrapping_add(1));
+                send_bytes(&mut reader, &request).await
+            }
+            Protocol::Ldap => {
+                send_bytes(&mut reader, &build_ldap_start_tls_request()).await?;
+                let message = read_ber(&mut reader).await?;
+                match parse_ldap_extended_response(&message)? {
+                    0 => Ok(()),
+                    code => bail!("LDAP server refused StartTLS with result code {0}", code),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Protocol {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "https" => Ok(Protocol::Https),
+            "smtp" => Ok(Protocol::Smtp),
+            "imap" => Ok(Protocol::Imap),
+            "pop3" => Ok(Protocol::Pop3),
+            "ftp" => Ok(Protocol::Ftp),
+            "xmpp" => Ok(Protocol::Xmpp),
+            "postgres" | "postgresql" => Ok(Protocol::Postgres),
+            "mysql" => Ok(Protocol::Mysql),
+            "ldap" => Ok(Protocol::Ldap),
+            _ => bail!("unsupported protocol {0}", s),
+        }
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Protocol::Https => "https",
+            Protocol::Smtp => "smtp",
+            Protocol::Imap => "imap",
+            Protocol::Pop3 => "pop3",
+            Protocol::Ftp => "ftp",
+            Protocol::Xmpp => "xmpp",
+            Protocol::Postgres => "postgres",
+            Protocol::Mysql => "mysql",
+            Protocol::Ldap => "ldap",
+            Protocol::File => "file",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+fn xmpp_stream_header(domain_name: &str) -> String {
+    format!(
+        concat!(
+            "<?xml version='1.0'?>",
+            "<stream:stream to='{0}' version='1.0' xmlns='jabber:client' ",
+            "xmlns:stream='http://etherx.jabber.org/streams'>"
+        ),
+        domain_name
+    )
+}
+
+async fn send<S: AsyncRead + AsyncWrite + Unpin>(
+    reader: &mut BufReader<S>,
+    s: &str,
+) -> anyhow::Result<()> {
+    send_bytes(reader, s.as_bytes()).await
+}
+
+async fn send_bytes<S: AsyncRead + AsyncWrite + Unpin>(
+    reader: &mut BufReader<S>,
+    bytes: &[u8],
+) -> anyhow::Result<()> {
+    let stream = reader.get_mut();
+    stream.write_all(bytes).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> anyhow::Result<String> {
+    let mut line = String::new();
+    let mut limited = reader.take(MAX_MESSAGE_BYTES as u64);
+    if limited.read_line(&mut line).await? == 0 {
+        bail!("connection closed before STARTTLS");
+    }
+    if !line.ends_with('\n') && line.len() == MAX_MESSAGE_BYTES {
+        bail!("line exceeds {0} bytes", MAX_MESSAGE_BYTES);
+    }
+    Ok(line)
+}
+
+/// Read SMTP or FTP reply, which might span multiple lines e.g. 250-... 250 ...
+async fn expect_reply<R: AsyncBufRead + Unpin>(reader: &mut R, code: &str) -> anyhow::Result<()> {
+    loop {
+        let line = read_line(reader).await?;
+        if !line.starts_with(code) {
+            bail!("expect reply {0} but got {1}", code, line.trim_end());
+        }
+        if line.as_bytes().get(code.len()) != Some(&b'-') {
+            return Ok(());
+        }
+    }
+}
+
+async fn read_xml_until<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    needle: &str,
+) -> anyhow::Result<String> {
+    let mut buf = vec![];
+    loop {
+        if buf.len() >= MAX_MESSAGE_BYTES {
+            bail!("XMPP stream exceeds {0} bytes", MAX_MESSAGE_BYTES);
+        }
+        let mut limited = (&mut *reader).take((MAX_MESSAGE_BYTES - buf.len()) as u64);
+        if limited.read_until(b'>', &mut buf).await? == 0 {
+            bail!("connection closed before STARTTLS");
+        }
+        let s = std::str::from_utf8(&buf).context("invalid XMPP stream")?;
+        if s.contains("<failure") || s.contains("</stream:stream>") {
+            bail!("XMPP server refused STARTTLS");
+        }
+        if s.contains(needle) {
+            return Ok(s.to_string());
+        }
+    }
+}
+
+/// Read one MySQL packet, which is prefixed with 3-byte length and sequence ID
+async fn read_mysql_packet<R: AsyncRead + Unpin>(reader: &mut R) -> anyhow::Result<(u8, Vec<u8>)> {
+    let mut header = [0; 4];
+    reader.read_exact(&mut header).await?;
+    let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+    if len > MAX_MESSAGE_BYTES {
+        bail!(
+            "MySQL packet of {0} bytes exceeds {1}",
+            len,
+            MAX_MESSAGE_BYTES
+        );
+    }
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload).await?;
+    Ok((header[3], payload))
+}
+
+/// Lower 2 bytes of capability flags from initial handshake packet
+fn parse_mysql_capabilities(payload: &[u8]) -> anyhow::Result<u16> {
+    match payload.first() {
+        Some(10) => (),
+        Some(0xff) => bail!("MySQL server refused connection"),
+        _ => bail!("unsupported MySQL handshake protocol"),
+    }
+    // protocol version, server version, connection ID, auth-plugin-data-part-1 and filler
+    let offset = payload[1..]
+        .iter()
+        .position(|b| *b == 0)
+        .context("invalid MySQL handshake packet")?
+        + 2
+        + 4
+        + 8
+        + 1;
+    match payload.get(offset..offset + 2) {
+        Some(b) => Ok(u16::from_le_bytes([b[0], b[1]])),
+        None => bail!("invalid MySQL handshake packet"),
+    }
+}
+
+fn build_mysql_ssl_request(sequence_id: u8) -> Vec<u8> {
+    let mut packet = vec![32, 0, 0, sequence_id];
+    packet.extend_from_slice(&MYSQL_SSL_REQUEST_CAPABILITIES.to_le_bytes());
+    // max packet size
+    packet.extend_from_slice(&0x0100_0000u32.to_le_bytes());
+    // utf8_general_ci
+    packet.push(33);
+    packet.extend_from_slice(&[0; 23]);
+    packet
+}
+
+/// LDAPMessage with message ID 1 and ExtendedRequest of StartTLS
+fn build_ldap_start_tls_request() -> Vec<u8> {
+    let oid_len = LDAP_START_TLS_OID.len() as u8;
+    let mut message = vec![
+        0x30,
+        oid_len + 7,
+        0x02,
+        0x01,
+        0x01,
+        0x77,
+        oid_len + 2,
+        0x80,
+        oid_len,
+    ];
+    message.extend_from_slice(LDAP_START_TLS_OID);
+    message
+}
+
+/// Read one BER-encoded element and return its content
+async fn read_ber<R: AsyncRead + Unpin>(reader: &mut R) -> anyhow::Result<Vec<u8>> {
+    let mut buf = [0; 2];
+    reader.read_exact(&mut buf).await?;
+    if buf[0] != 0x30 {
+        bail!("unexpected LDAP message tag {0:#04x}", buf[0]);
+    }
+    let len = match buf[1] {
+        len if len < 0x80 => len as usize,
+        len => {
+            let n = (len & 0x7f) as usize;
+            if n == 0 || n > 4 {
+                bail!("invalid LDAP message length");
+            }
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes[4 - n..]).await?;
+            u32::from_be_bytes(bytes) as usize
+        }
+    };
+    if len > MAX_MESSAGE_BYTES {
+        bail!(
+            "LDAP message of {0} bytes exceeds {1}",
+            len,
+            MAX_MESSAGE_BYTES
+        );
+    }
+    let mut content = vec![0; len];
+    reader.read_exact(&mut content).await?;
+    Ok(content)
+}
+
+/// Split BER element into tag, content and rest
+fn split_ber(bytes: &[u8]) -> anyhow::Result<(u8, &[u8], &[u8])> {
+    if bytes.len() < 2 {
+        bail!("truncated LDAP message");
+    }
+    let (len, offset) = match bytes[1] {
+        len if len < 0x80 => (len as usize, 2),
+        len => {
+            let n = (len & 0x7f) as usize;
+            if n == 0 || n > 4 || bytes.len() < 2 + n {
+                bail!("invalid LDAP message length");
+            }
+            let len = bytes[2..2 + n]
+                .iter()
+                .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+            (len, 2 + n)
+        }
+    };
+    match bytes.get(offset..offset + len) {
+        Some(content) => Ok((bytes[0], content, &bytes[offset + len..])),
+        None => bail!("truncated LDAP message"),
+    }
+}
+
+/// Result code of ExtendedResponse in LDAPMessage
+fn parse_ldap_extended_response(message: &[u8]) -> anyhow::Result<u8> {
+    let (tag, _, rest) = split_ber(message)?;
+    if tag != 0x02 {
+        bail!("missing LDAP message ID");
+    }
+    let (tag, response, _) = split_ber(rest)?;
+    if tag != 0x78 {
+        bail!("unexpected LDAP response tag {0:#04x}", tag);
+    }
+    match split_ber(response)? {
+        (0x0a, [code], _) => Ok(*code),
+        _ => bail!("missing LDAP result code"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+    use std::str::FromStr;
+
+    use crate::protocol::{
+        build_ldap_start_tls_request, expect_reply, parse_ldap_extended_response,
+        parse_mysql_capabilities, read_ber, read_line, read_mysql_packet, read_xml_until, Protocol,
+        MAX_MESSAGE_BYTES,
+    };
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(Protocol::Smtp, Protocol::from_str("SMTP").unwrap());
+        assert_eq!(Protocol::Xmpp, Protocol::from_str("xmpp").unwrap());
+        assert_eq!(
+            Protocol::Postgres,
+            Protocol::from_str("postgresql").unwrap()
+        );
+        assert!(Protocol::from_str("gopher").is_err());
+    }
+
+    #[test]
+    fn test_ldap_start_tls_request() {
+        let request = build_ldap_start_tls_request();
+        assert_eq!(31, request.len());
+        assert_eq!(
+            &[0x30, 0x1d, 0x02, 0x01, 0x01, 0x77, 0x18, 0x80, 0x16],
+            &request[..9]
+        );
+    }
+
+    #[test]
+    fn test_parse_ldap_extended_response() {
+        let message = [
+            0x02, 0x01, 0x01, 0x78, 0x07, 0x0a, 0x01, 0x00, 0x04, 0x00, 0x04, 0x00,
+        ];
+        assert_eq!(0, parse_ldap_extended_response(&message).unwrap());
+
+        let message = [
+            0x02, 0x01, 0x01, 0x78, 0x07, 0x0a, 0x01, 0x02, 0x04, 0x00, 0x04, 0x00,
+        ];
+        assert_eq!(2, parse_ldap_extended_response(&message).unwrap());
+
+        assert!(parse_ldap_extended_response(&[0x02, 0x01]).is_err());
+    }
+
+    #[test]
+    fn test_parse_mysql_capabilities() {
+        let mut payload = vec![10];
+        payload.extend_from_slice(b"8.0.0\0");
+        payload.extend_from_slice(&[1, 0, 0, 0]);
+        payload.extend_from_slice(b"abcdefgh\0");
+        payload.extend_from_slice(&[0x00, 0x8a]);
+        assert_eq!(0x8a00, parse_mysql_capabilities(&payload).unwrap());
+
+        assert!(parse_mysql_capabilities(&[0xff, 0x15, 0x04]).is_err());
+        assert!(parse_mysql_capabilities(&payload[..10]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_expect_reply_multiline() {
+        let mut reader = Cursor::new("250-example.com\r\n250-SIZE 1000\r\n250 STARTTLS\r\n");
+        expect_reply(&mut reader, "250").await.unwrap();
+        assert_eq!(reader.position() as usize, reader.get_ref().len());
+
+        let mut reader = Cursor::new("454 TLS not available\r\n");
+        assert!(expect_reply(&mut reader, "220").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_message() {
+        // lengths are rejected before payload arrives
+        let mut reader = Cursor::new(vec![0x30, 0x84, 0xff, 0xff, 0xff, 0xff]);
+        assert!(read_ber(&mut reader).await.is_err());
+        let mut reader = Cursor::new(vec![0xff, 0xff, 0xff, 0x00]);
+        assert!(read_mysql_packet(&mut reader).await.is_err());
+
+        let mut reader = Cursor::new(vec![0x30, 0x03, 0x02, 0x01, 0x01]);
+        assert_eq!(vec![0x02, 0x01, 0x01], read_ber(&mut reader).await.unwrap());
+
+        let long = "2".repeat(MAX_MESSAGE_BYTES + 1);
+        let mut reader = Cursor::new(long.clone());
+        assert!(read_line(&mut reader).await.is_err());
+        let mut reader = Cursor::new(long);
+        assert!(read_xml_until(&mut reader, "</stream:features>")
+            .await
+            .is_err());
+
+        let line = format!("{0}\n", "2".repeat(MAX_MESSAGE_BYTES - 1));
+        let mut reader = Cursor::new(line.clone());
+        assert_eq!(line, read_line(&mut reader).await.unwrap());
+    }
+}
@@ -0,0 +1,556 @@
+//! Revocation status from OCSP responses and certificate revocation lists
+use std::fmt;
+
+use anyhow::{anyhow, bail, Context};
+use chrono::NaiveDateTime;
+use ring::digest;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use x509_parser::certificate::X509Certificate;
+use x509_parser::extensions::{GeneralName, ParsedExtension};
+
+use crate::der::{
+    der, expect_der, read_der, TAG_BIT_STRING, TAG_CONTEXT_0, TAG_CONTEXT_1, TAG_ENUMERATED,
+    TAG_GENERALIZED_TIME, TAG_INTEGER, TAG_NULL, TAG_OCTET_STRING, TAG_OID, TAG_SEQUENCE,
+};
+
+/// Largest response accepted from OCSP responder or CRL distribution point,
+/// well above CRLs of public certificate authorities
+const MAX_RESPONSE_BYTES: u64 = 16 * 1024 * 1024;
+
+/// URI in general name
+pub(crate) const TAG_URI: u8 = 0x86;
+
+/// Certificate status good, [0] IMPLICIT NULL
+pub(crate) const TAG_GOOD: u8 = 0x80;
+/// Certificate status unknown, [2] IMPLICIT NULL
+#[cfg(test)]
+pub(crate) const TAG_UNKNOWN: u8 = 0x82;
+
+/// SHA-1, 1.3.14.3.2.26
+pub(crate) const OID_SHA1: &[u8] = &[0x2b, 0x0e, 0x03, 0x02, 0x1a];
+/// SHA-256, 2.16.840.1.101.3.4.2.1
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+/// id-pkix-ocsp-basic, 1.3.6.1.5.5.7.48.1.1
+pub(crate) const OID_OCSP_BASIC: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x01];
+/// id-ad-ocsp, 1.3.6.1.5.5.7.48.1
+pub(crate) const OID_AD_OCSP: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01];
+/// id-ce-cRLDistributionPoints, 2.5.29.31
+const OID_CRL_DISTRIBUTION_POINTS: &[u8] = &[0x55, 0x1d, 0x1f];
+/// ecdsa-with-SHA256, 1.2.840.10045.4.3.2
+pub(crate) const OID_ECDSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+/// ecdsa-with-SHA384, 1.2.840.10045.4.3.3
+const OID_ECDSA_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+/// sha256WithRSAEncryption, 1.2.840.113549.1.1.11
+const OID_RSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+/// sha384WithRSAEncryption, 1.2.840.113549.1.1.12
+const OID_RSA_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c];
+/// sha512WithRSAEncryption, 1.2.840.113549.1.1.13
+const OID_RSA_SHA512: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d];
+/// Ed25519, 1.3.101.112
+const OID_ED25519: &[u8] = &[0x2b, 0x65, 0x70];
+
+/// Revocation status of leaf certificate
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RevocationStatus {
+    /// Certificate is not revoked
+    Good,
+    /// Certificate is revoked by issuer
+    Revoked,
+    /// Status cannot be determined, e.g. response is missing, stale or not signed by issuer
+    Unknown,
+}
+
+impl fmt::Display for RevocationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RevocationStatus::Good => write!(f, "GOOD"),
+            RevocationStatus::Revoked => write!(f, "REVOKED"),
+            RevocationStatus::Unknown => write!(f, "UNKNOWN"),
+        }
+    }
+}
+
+/// Serial number, subject name and public key of certificate
+struct Identity<'a> {
+    serial: &'a [u8],
+    subject: &'a [u8],
+    public_key: &'a [u8],
+}
+
+impl<'a> Identity<'a> {
+    fn parse(certificate: &'a X509Certificate<'a>) -> Self {
+        let tbs = &certificate.tbs_certificate;
+        Identity {
+            serial: tbs.raw_serial(),
+            subject: tbs.subject.as_raw(),
+            public_key: tbs.subject_pki.subject_public_key.data,
+        }
+    }
+}
+
+/// CertID of leaf certificate with SHA-1 hashes, in OCSP requests and responses
+pub(crate) fn cert_id(leaf: &[u8], issuer: &[u8]) -> Option<Vec<u8>> {
+    let (_, leaf) = x509_parser::parse_x509_certificate(leaf).ok()?;
+    let (_, issuer) = x509_parser::parse_x509_certificate(issuer).ok()?;
+    let (leaf, issuer) = (Identity::parse(&leaf), Identity::parse(&issuer));
+    let sha1 = &digest::SHA1_FOR_LEGACY_USE_ONLY;
+    let algorithm = [der(TAG_OID, OID_SHA1), der(TAG_NULL, &[])].concat();
+    let content = [
+        der(TAG_SEQUENCE, &algorithm),
+        der(
+            TAG_OCTET_STRING,
+            digest::digest(sha1, issuer.subject).as_ref(),
+        ),
+        der(
+            TAG_OCTET_STRING,
+            digest::digest(sha1, issuer.public_key).as_ref(),
+        ),
+        der(TAG_INTEGER, leaf.serial),
+    ]
+    .concat();
+    Some(der(TAG_SEQUENCE, &content))
+}
+
+/// OCSP request for leaf certificate without extensions
+pub(crate) fn ocsp_request(leaf: &[u8], issuer: &[u8]) -> Option<Vec<u8>> {
+    let request = der(TAG_SEQUENCE, &cert_id(leaf, issuer)?);
+    let request_list = der(TAG_SEQUENCE, &request);
+    let tbs_request = der(TAG_SEQUENCE, &request_list);
+    Some(der(TAG_SEQUENCE, &tbs_request))
+}
+
+/// Whether CertID in OCSP response identifies leaf certificate, hashed with SHA-1 or SHA-256
+fn matches_cert_id(cert_id: &[u8], leaf: &Identity, issuer: &Identity) -> Option<bool> {
+    let (_, algorithm, rest) = expect_der(cert_id, TAG_SEQUENCE)?;
+    let (_, oid, _) = expect_der(algorithm, TAG_OID)?;
+    let algorithm = match oid {
+        OID_SHA1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        OID_SHA256 => &digest::SHA256,
+        _ => return None,
+    };
+    let (_, name_hash, rest) = expect_der(rest, TAG_OCTET_STRING)?;
+    let (_, key_hash, rest) = expect_der(rest, TAG_OCTET_STRING)?;
+    let (_, serial, _) = expect_der(rest, TAG_INTEGER)?;
+    Some(
+        serial == leaf.serial
+            && name_hash == digest::digest(algorithm, issuer.subject).as_ref()
+            && key_hash == digest::digest(algorithm, issuer.public_key).as_ref(),
+    )
+}
+
+/// Status of leaf certificate in OCSP response signed by issuer or its delegated responder,
+/// unknown if response is unsuccessful, unsigned or stale
+pub(crate) fn ocsp_status(
+    response: &[u8],
+    leaf: &[u8],
+    issuer: &[u8],
+    now: i64,
+) -> RevocationStatus {
+    parse_ocsp(response, leaf, issuer, now).unwrap_or(RevocationStatus::Unknown)
+}
+
+fn parse_ocsp(response: &[u8], leaf: &[u8], issuer: &[u8], now: i64) -> Option<RevocationStatus> {
+    let (_, response, _) = expect_der(response, TAG_SEQUENCE)?;
+    let (_, status, rest) = expect_der(response, TAG_ENUMERATED)?;
+    // successful
+    if status != [0] {
+        return None;
+    }
+    let (_, response_bytes, _) = expect_der(rest, TAG_CONTEXT_0)?;
+    let (_, response_bytes, _) = expect_der(response_bytes, TAG_SEQUENCE)?;
+    let (_, oid, rest) = expect_der(response_bytes, TAG_OID)?;
+    if oid != OID_OCSP_BASIC {
+        return None;
+    }
+    let (_, basic, _) = expect_der(rest, TAG_OCTET_STRING)?;
+    let (_, basic, _) = expect_der(basic, TAG_SEQUENCE)?;
+    let (tbs, response_data, rest) = expect_der(basic, TAG_SEQUENCE)?;
+    let (_, algorithm, rest) = expect_der(rest, TAG_SEQUENCE)?;
+    let (_, algorithm, _) = expect_der(algorithm, TAG_OID)?;
+    let (_, signature, rest) = expect_der(rest, TAG_BIT_STRING)?;
+    let signature = signature.get(1..)?;
+    let mut responders = vec![];
+    if let Some((_, certs, _)) = expect_der(rest, TAG_CONTEXT_0) {
+        let (_, mut certs, _) = expect_der(certs, TAG_SEQUENCE)?;
+        while let Some((_, cert, _, rest)) = read_der(certs) {
+            responders.push(cert);
+            certs = rest;
+        }
+    }
+    let signed = verify_signature(issuer, algorithm, tbs, signature)
+        || responders.iter().any(|responder| {
+            is_delegated_responder(responder, issuer)
+                && verify_signature(responder, algorithm, tbs, signature)
+        });
+    if !signed {
+        return None;
+    }
+
+    let (_, leaf) = x509_parser::parse_x509_certificate(leaf).ok()?;
+    let (_, issuer) = x509_parser::parse_x509_certificate(issuer).ok()?;
+    let (leaf, issuer) = (Identity::parse(&leaf), Identity::parse(&issuer));
+    let mut rest = response_data;
+    // optional version, responder ID and production time
+    if let Some((_, _, rest_)) = expect_der(rest, TAG_CONTEXT_0) {
+        rest = rest_;
+    }
+    let (_, _, _, rest) = read_der(rest)?;
+    let (_, _, rest) = expect_der(rest, TAG_GENERALIZED_TIME)?;
+    let (_, mut responses, _) = expect_der(rest, TAG_SEQUENCE)?;
+    while let Some((_, single, rest)) = expect_der(responses, TAG_SEQUENCE) {
+        responses = rest;
+        let (_, cert_id, rest) = expect_der(single, TAG_SEQUENCE)?;
+        if !matches_cert_id(cert_id, &leaf, &issuer)? {
+            continue;
+        }
+        let (status, _, _, rest) = read_der(rest)?;
+        let (_, _, rest) = expect_der(rest, TAG_GENERALIZED_TIME)?;
+        if let Some((_, next_update, _)) = expect_der(rest, TAG_CONTEXT_0) {
+            let (_, next_update, _) = expect_der(next_update, TAG_GENERALIZED_TIME)?;
+            if parse_time(next_update)? < now {
+                return Some(RevocationStatus::Unknown);
+            }
+        }
+        return Some(match status {
+            TAG_GOOD => RevocationStatus::Good,
+            TAG_CONTEXT_1 => RevocationStatus::Revoked,
+            _ => RevocationStatus::Unknown,
+        });
+    }
+    None
+}
+
+/// Timestamp of GeneralizedTime e.g. 20210601120000Z, fraction of second is ignored
+fn parse_time(time: &[u8]) -> Option<i64> {
+    let time = std::str::from_utf8(time.get(..14)?).ok()?;
+    let time = NaiveDateTime::parse_from_str(time, "%Y%m%d%H%M%S").ok()?;
+    Some(time.timestamp())
+}
+
+/// Certificate among candidates which issued leaf, named as issuer of leaf
+/// and with key that verifies signature of leaf
+pub(crate) fn find_issuer<'a>(
+    leaf: &[u8],
+    candidates: impl IntoIterator<Item = &'a [u8]>,
+) -> Option<&'a [u8]> {
+    let (_, parsed) = x509_parser::parse_x509_certificate(leaf).ok()?;
+    let name = parsed.tbs_certificate.issuer.as_raw();
+    candidates.into_iter().find(|candidate| {
+        let named = matches!(
+            x509_parser::parse_x509_certificate(candidate),
+            Ok((_, candidate)) if candidate.tbs_certificate.subject.as_raw() == name
+        );
+        named && is_signed_by(leaf, candidate)
+    })
+}
+
+/// Whether responder certificate is issued by issuer for signing OCSP responses
+fn is_delegated_responder(responder: &[u8], issuer: &[u8]) -> bool {
+    let ocsp_signing = match x509_parser::parse_x509_certificate(responder) {
+        Ok((_, responder)) => matches!(
+            responder.tbs_certificate.extended_key_usage(),
+            Some((_, eku)) if eku.ocscp_signing
+        ),
+        Err(_) => false,
+    };
+    ocsp_signing && is_signed_by(responder, issuer)
+}
+
+/// Whether certificate or CRL is signed by issuer
+fn is_signed_by(signed: &[u8], issuer: &[u8]) -> bool {
+    let parse = || {
+        let (_, signed, _) = expect_der(signed, TAG_SEQUENCE)?;
+        let (tbs, _, rest) = expect_der(signed, TAG_SEQUENCE)?;
+        let (_, algorithm, rest) = expect_der(rest, TAG_SEQUENCE)?;
+        let (_, algorithm, _) = expect_der(algorithm, TAG_OID)?;
+        let (_, signature, _) = expect_der(rest, TAG_BIT_STRING)?;
+        Some(verify_signature(
+            issuer,
+            algorithm,
+            tbs,
+            signature.get(1..)?,
+        ))
+    };
+    parse().unwrap_or(false)
+}
+
+/// Whether message is signed by key of certificate with signature algorithm
+fn verify_signature(signer: &[u8], algorithm: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    let algorithms: &[&webpki::SignatureAlgorithm] = match algorithm {
+        OID_ECDSA_SHA256 => &[&webpki::ECDSA_P256_SHA256, &webpki::ECDSA_P384_SHA256],
+        OID_ECDSA_SHA384 => &[&webpki::ECDSA_P256_SHA384, &webpki::ECDSA_P384_SHA384],
+        OID_RSA_SHA256 => &[&webpki::RSA_PKCS1_2048_8192_SHA256],
+        OID_RSA_SHA384 => &[&webpki::RSA_PKCS1_2048_8192_SHA384],
+        OID_RSA_SHA512 => &[&webpki::RSA_PKCS1_2048_8192_SHA512],
+        OID_ED25519 => &[&webpki::ED25519],
+        _ => return false,
+    };
+    let signer = match webpki::EndEntityCert::from(signer) {
+        Ok(signer) => signer,
+        Err(_) => return false,
+    };
+    algorithms.iter().any(|algorithm| {
+        signer
+            .verify_signature(algorithm, message, signature)
+            .is_ok()
+    })
+}
+
+/// Status of leaf certificate in certificate revocation list signed by issuer,
+/// unknown if list is unsigned or stale
+pub(crate) fn crl_status(crl: &[u8], leaf: &[u8], issuer: &[u8], now: i64) -> RevocationStatus {
+    parse_crl(crl, leaf, issuer, now).unwrap_or(RevocationStatus::Unknown)
+}
+
+fn parse_crl(crl: &[u8], leaf: &[u8], issuer: &[u8], now: i64) -> Option<RevocationStatus> {
+    if !is_signed_by(crl, issuer) {
+        return None;
+    }
+    let (_, crl) = x509_parser::parse_x509_crl(crl).ok()?;
+    let (_, leaf) = x509_parser::parse_x509_certificate(leaf).ok()?;
+    let (_, issuer) = x509_parser::parse_x509_certificate(issuer).ok()?;
+    if crl.issuer().as_raw() != issuer.tbs_certificate.subject.as_raw() {
+        return None;
+    }
+    if matches!(crl.next_update(), Some(next_update) if next_update.timestamp() < now) {
+        return Some(RevocationStatus::Unknown);
+    }
+    let serial = leaf.tbs_certificate.raw_serial();
+    let revoked = crl
+        .iter_revoked_certificates()
+        .any(|revoked| revoked.raw_serial() == serial);
+    Some(if revoked {
+        RevocationStatus::Revoked
+    } else {
+        RevocationStatus::Good
+    })
+}
+
+/// URLs of OCSP responders in authority information access extension
+pub(crate) fn ocsp_urls(certificate: &[u8]) -> Vec<String> {
+    let certificate = match x509_parser::parse_x509_certificate(certificate) {
+        Ok((_, certificate)) => certificate,
+        Err(_) => return vec![],
+    };
+    let mut urls = vec![];
+    for extension in certificate.extensions().values() {
+        if let ParsedExtension::AuthorityInfoAccess(aia) = extension.parsed_extension() {
+            for (method, names) in aia.accessdescs.iter() {
+                if method.bytes() != OID_AD_OCSP {
+                    continue;
+                }
+                for name in names.iter() {
+                    if let GeneralName::URI(uri) = name {
+                        urls.push(uri.to_string());
+                    }
+                }
+            }
+        }
+    }
+    urls
+}
+
+/// URLs of certificate revocation lists in CRL distribution points extension
+pub(crate) fn crl_urls(certificate: &[u8]) -> Vec<String> {
+    let certificate = match x509_parser::parse_x509_certificate(certificate) {
+        Ok((_, certificate)) => certificate,
+        Err(_) => return vec![],
+    };
+    let mut urls = vec![];
+    for (oid, extension) in certificate.extensions().iter() {
+        if oid.bytes() == OID_CRL_DISTRIBUTION_POINTS {
+            collect_uris(extension.value, &mut urls);
+        }
+    }
+    urls
+}
+
+/// URIs in nested DER elements
+fn collect_uris(mut input: &[u8], uris: &mut Vec<String>) {
+    while let Some((tag, _, content, rest)) = read_der(input) {
+        if tag == TAG_URI {
+            uris.push(String::from_utf8_lossy(content).to_string());
+        } else if tag & 0x20 != 0 {
+            collect_uris(content, uris);
+        }
+        input = rest;
+    }
+}
+
+/// Body of response to HTTP GET, or to POST of OCSP request when it is given.
+/// Only plain HTTP is supported, which OCSP responders and CRL distribution points use
+pub(crate) async fn fetch(url: &str, ocsp_request: Option<&[u8]>) -> anyhow::Result<Vec<u8>> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| anyhow!("unsupported URL {0}", url))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rfind(':') {
+        Some(i) if !authority.ends_with(']') => (&authority[..i], authority[i + 1..].parse()?),
+        _ => (authority, 80),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    let mut request = match ocsp_request {
+        Some(body) => format!(
+            "POST {0} HTTP/1.0\r\nHost: {1}\r\nContent-Type: application/ocsp-request\r\nContent-Length: {2}\r\n\r\n",
+            path,
+            authority,
+            body.len()
+        )
+        .into_bytes(),
+        None => format!("GET {0} HTTP/1.0\r\nHost: {1}\r\n\r\n", path, authority).into_bytes(),
+    };
+    request.extend_from_slice(ocsp_request.unwrap_or_default());
+    let mut sock = TcpStream::connect((host, port)).await?;
+    sock.write_all(&request).await?;
+    let mut response = vec![];
+    sock.take(MAX_RESPONSE_BYTES + 1)
+        .read_to_end(&mut response)
+        .await?;
+    if response.len() as u64 > MAX_RESPONSE_BYTES {
+        bail!(
+            "response from {0} exceeds {1} bytes",
+            url,
+            MAX_RESPONSE_BYTES
+        );
+    }
+
+    let i = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .with_context(|| format!("malformed response from {0}", url))?;
+    let head = String::from_utf8_lossy(&response[..i]);
+    let status = head.split_whitespace().nth(1).unwrap_or_default();
+    if status != "200" {
+        bail!("{0} responded with status {1}", url, status);
+    }
+    Ok(response.split_off(i + 4))
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, Utc};
+
+    use crate::fixtures::{http_listener, serve_http, TestServer};
+    use crate::revocation::{
+        cert_id, crl_status, crl_urls, fetch, find_issuer, ocsp_request, ocsp_status, ocsp_urls,
+        RevocationStatus, MAX_RESPONSE_BYTES,
+    };
+
+    #[test]
+    fn test_ocsp_status() {
+        let url = "http://127.0.0.1:1";
+        let server = TestServer::with_revocation_url(url);
+        let (leaf, issuer) = (&server.chain()[0].0, &server.chain()[1].0);
+        let now = Utc::now().timestamp();
+        let next_update = Utc::now() + Duration::days(1);
+
+        let request = ocsp_request(leaf, issuer).unwrap();
+        let id = cert_id(leaf, issuer).unwrap();
+        assert!(request.windows(id.len()).any(|w| w == id.as_slice()));
+
+        for status in &[
+            RevocationStatus::Good,
+            RevocationStatus::Revoked,
+            RevocationStatus::Unknown,
+        ] {
+            let response = server.ocsp_response(*status, next_update);
+            assert_eq!(*status, ocsp_status(&response, leaf, issuer, now));
+        }
+
+        // stale response
+        let response = server.ocsp_response(RevocationStatus::Good, Utc::now() - Duration::days(1));
+        let status = ocsp_status(&response, leaf, issuer, now);
+        assert_eq!(RevocationStatus::Unknown, status);
+
+        // response is not signed by issuer of leaf certificate
+        let other = TestServer::with_revocation_url(url);
+        let response = other.ocsp_response(RevocationStatus::Revoked, next_update);
+        let status = ocsp_status(&response, leaf, issuer, now);
+        assert_eq!(RevocationStatus::Unknown, status);
+
+        let status = ocsp_status(b"garbage", leaf, issuer, now);
+        assert_eq!(RevocationStatus::Unknown, status);
+    }
+
+    #[test]
+    fn test_crl_status() {
+        let server = TestServer::with_revocation_url("http://127.0.0.1:1");
+        let (leaf, issuer) = (&server.chain()[0].0, &server.chain()[1].0);
+        let now = Utc::now().timestamp();
+
+        let crl = server.crl(false);
+        assert_eq!(RevocationStatus::Good, crl_status(&crl, leaf, issuer, now));
+        let crl = server.crl(true);
+        assert_eq!(
+            RevocationStatus::Revoked,
+            crl_status(&crl, leaf, issuer, now)
+        );
+
+        let other = TestServer::with_revocation_url("http://127.0.0.1:1");
+        let crl = other.crl(true);
+        assert_eq!(
+            RevocationStatus::Unknown,
+            crl_status(&crl, leaf, issuer, now)
+        );
+    }
+
+    #[test]
+    fn test_find_issuer() {
+        let server = TestServer::with_revocation_url("http://127.0.0.1:1");
+        let (leaf, issuer) = (&server.chain()[0].0, &server.chain()[1].0);
+        // named like the issuer but with another key
+        let (forged, _) =
+            server.forged_ocsp_response("hcc test intermediate CA", RevocationStatus::Good);
+        let (other, _) = server.forged_ocsp_response("hcc forged CA", RevocationStatus::Good);
+
+        let candidates = [forged.0.as_slice(), &other.0, issuer];
+        assert_eq!(
+            Some(issuer.as_slice()),
+            find_issuer(leaf, candidates.iter().copied())
+        );
+        let candidates = [forged.0.as_slice(), &other.0];
+        assert_eq!(None, find_issuer(leaf, candidates.iter().copied()));
+        assert_eq!(None, find_issuer(b"garbage", vec![issuer.as_slice()]));
+    }
+
+    #[test]
+    fn test_urls() {
+        let server = TestServer::with_revocation_url("http://127.0.0.1:8080");
+        let leaf = &server.chain()[0].0;
+        assert_eq!(vec!["http://127.0.0.1:8080/ocsp"], ocsp_urls(leaf));
+        assert_eq!(vec!["http://127.0.0.1:8080/crl"], crl_urls(leaf));
+
+        let server = TestServer::new();
+        assert!(ocsp_urls(&server.chain()[0].0).is_empty());
+        assert!(crl_urls(&server.chain()[0].0).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch() {
+        let (listener, url) = http_listener();
+        serve_http(
+            listener,
+            vec![
+                ("/crl", b"list".to_vec()),
+                ("/ocsp", b"response".to_vec()),
+                ("/large", vec![0; MAX_RESPONSE_BYTES as usize]),
+            ],
+        );
+        let body = fetch(&format!("{0}/crl", url), None).await.unwrap();
+        assert_eq!(b"list".to_vec(), body);
+        let body = fetch(&format!("{0}/ocsp", url), Some(b"request"))
+            .await
+            .unwrap();
+        assert_eq!(b"response".to_vec(), body);
+        assert!(fetch(&format!("{0}/missing", url), None).await.is_err());
+        assert!(fetch("https://example.com/crl", None).await.is_err());
+        // head and body together exceed the limit
+        let e = fetch(&format!("{0}/large", url), None).await.unwrap_err();
+        assert!(e.to_string().contains("exceeds"));
+    }
+}
@@ -0,0 +1,121 @@
+//! Loading trusted certificate authorities into root store
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use rustls::RootCertStore;
+
+/// Add certificate authorities in PEM bundle, returns number of certificates added
+pub(crate) fn add_pem_file(store: &mut RootCertStore, path: &Path) -> anyhow::Result<usize> {
+    match add_pem(store, path)? {
+        0 => bail!("no valid certificate in {0}", path.display()),
+        added => Ok(added),
+    }
+}
+
+/// Add certificate authorities in every PEM file of directory, files without
+/// certificate are skipped, e.g. README or CRL, while malformed ones are errors
+pub(crate) fn add_pem_dir(store: &mut RootCertStore, path: &Path) -> anyhow::Result<usize> {
+    let entries =
+        fs::read_dir(path).with_context(|| format!("failed to read {0}", path.display()))?;
+    let mut added = 0;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file() {
+            added += add_pem(store, &path)?;
+        }
+    }
+    if added == 0 {
+        bail!("no valid certificate in {0}", path.display());
+    }
+    Ok(added)
+}
+
+/// Add certificate authorities in PEM file, returns 0 if it contains no certificate,
+/// fails when it cannot be read or none of its certificates is valid
+fn add_pem(store: &mut RootCertStore, path: &Path) -> anyhow::Result<usize> {
+    let bytes = fs::read(path).with_context(|| format!("failed to read {0}", path.display()))?;
+    if !bytes
+        .windows(27)
+        .any(|w| w == b"-----BEGIN CERTIFICATE-----")
+    {
+        return Ok(0);
+    }
+    let certificates = match rustls::internal::pemfile::certs(&mut bytes.as_slice()) {
+        Ok(certificates) => certificates,
+        Err(_) => bail!("invalid PEM in {0}", path.display()),
+    };
+    let added = certificates.iter().filter(|c| store.add(c).is_ok()).count();
+    if added == 0 {
+        bail!("no valid certificate in {0}", path.display());
+    }
+    Ok(added)
+}
+
+/// Add certificate authorities trusted by operating system
+pub(crate) fn add_native(store: &mut RootCertStore) -> anyhow::Result<usize> {
+    let native = match rustls_native_certs::load_native_certs() {
+        Ok(native) => native,
+        // some certificates may be loaded despite of error
+        Err((Some(native), _)) => native,
+        Err((None, e)) => return Err(e).context("failed to load native root certificates"),
+    };
+    let added = native.roots.len();
+    store.roots.extend(native.roots);
+    Ok(added)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use rustls::RootCertStore;
+
+    use crate::fixtures::{temp_dir, TestServer};
+    use crate::roots::{add_pem_dir, add_pem_file};
+
+    #[test]
+    fn test_add_pem_file() {
+        let dir = temp_dir("add_pem_file");
+        let path = dir.join("ca.pem");
+        fs::write(&path, TestServer::new().ca_pem()).unwrap();
+
+        let mut store = RootCertStore::empty();
+        assert_eq!(1, add_pem_file(&mut store, &path).unwrap());
+        assert_eq!(1, store.len());
+
+        let path = dir.join("empty.pem");
+        fs::write(&path, "").unwrap();
+        assert!(add_pem_file(&mut store, &path).is_err());
+        assert!(add_pem_file(&mut store, &PathBuf::from("/nonexistent.pem")).is_err());
+    }
+
+    #[test]
+    fn test_add_pem_dir() {
+        let dir = temp_dir("add_pem_dir");
+        fs::write(dir.join("a.pem"), TestServer::new().ca_pem()).unwrap();
+        fs::write(dir.join("b.crt"), TestServer::new().ca_pem()).unwrap();
+        fs::write(dir.join("README"), "not a certificate").unwrap();
+        fs::write(dir.join("index.bin"), [0xff, 0xfe, 0x00]).unwrap();
+
+        let mut store = RootCertStore::empty();
+        assert_eq!(2, add_pem_dir(&mut store, &dir).unwrap());
+
+        let dir = temp_dir("add_pem_dir_empty");
+        assert!(add_pem_dir(&mut store, &dir).is_err());
+
+        // malformed certificate is reported instead of skipped
+        let dir = temp_dir("add_pem_dir_malformed");
+        fs::write(dir.join("a.pem"), TestServer::new().ca_pem()).unwrap();
+        let malformed = "-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n";
+        fs::write(dir.join("b.pem"), malformed).unwrap();
+        let e = add_pem_dir(&mut store, &dir).unwrap_err();
+        assert!(e.to_string().contains("b.pem"));
+
+        let truncated = "-----BEGIN CERTIFICATE-----\n!!!!\n-----END CERTIFICATE-----\n";
+        fs::write(dir.join("b.pem"), truncated).unwrap();
+        let e = add_pem_dir(&mut store, &dir).unwrap_err();
+        assert!(e.to_string().contains("b.pem"));
+    }
+}
@@ -0,0 +1,614 @@
+//! Scanning protocol versions and cipher suites accepted by server
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use anyhow::bail;
+use rustls::ProtocolVersion;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::check_result::ErrorKind;
+use crate::protocol::Protocol;
+use crate::target::Target;
+
+/// Cipher suites which rustls cannot negotiate, probed with bare ClientHello,
+/// and whether they are only defined for TLS 1.2
+const LEGACY_SUITES: &[(u16, &str, bool)] = &[
+    (0xc009, "TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA", false),
+    (0xc00a, "TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA", false),
+    (0xc023, "TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA256", true),
+    (0xc024, "TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA384", true),
+    (0xc013, "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA", false),
+    (0xc014, "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA", false),
+    (0xc027, "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA256", true),
+    (0xc028, "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA384", true),
+    (0x009c, "TLS_RSA_WITH_AES_128_GCM_SHA256", true),
+    (0x009d, "TLS_RSA_WITH_AES_256_GCM_SHA384", true),
+    (0x002f, "TLS_RSA_WITH_AES_128_CBC_SHA", false),
+    (0x0035, "TLS_RSA_WITH_AES_256_CBC_SHA", false),
+    (0x003c, "TLS_RSA_WITH_AES_128_CBC_SHA256", true),
+    (0x000a, "TLS_RSA_WITH_3DES_EDE_CBC_SHA", false),
+];
+
+/// Cipher suite offered in scan
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Suite {
+    /// Identifier in ClientHello and ServerHello
+    pub(crate) id: u16,
+    /// IANA name
+    pub(crate) name: String,
+    /// Whether rustls can negotiate it
+    pub(crate) rustls: bool,
+}
+
+/// Version of TLS protocol
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    /// TLS 1.0
+    Tls10,
+    /// TLS 1.1
+    Tls11,
+    /// TLS 1.2
+    Tls12,
+    /// TLS 1.3
+    Tls13,
+}
+
+impl TlsVersion {
+    /// Versions in the order they are scanned, the newest first
+    pub(crate) const ALL: [TlsVersion; 4] = [
+        TlsVersion::Tls13,
+        TlsVersion::Tls12,
+        TlsVersion::Tls11,
+        TlsVersion::Tls10,
+    ];
+
+    /// Version in ClientHello and ServerHello
+    fn wire(&self) -> u16 {
+        match self {
+            TlsVersion::Tls10 => 0x0301,
+            TlsVersion::Tls11 => 0x0302,
+            TlsVersion::Tls12 => 0x0303,
+            TlsVersion::Tls13 => 0x0304,
+        }
+    }
+
+    /// Version in rustls, None if rustls cannot negotiate it
+    pub(crate) fn rustls(&self) -> Option<ProtocolVersion> {
+        match self {
+            TlsVersion::Tls12 => Some(ProtocolVersion::TLSv1_2),
+            TlsVersion::Tls13 => Some(ProtocolVersion::TLSv1_3),
+            _ => None,
+        }
+    }
+
+    /// Cipher suites defined for version, those negotiated by rustls first
+    pub(crate) fn suites(&self) -> Vec<Suite> {
+        let mut suites: Vec<Suite> = match self.rustls() {
+            Some(version) => rustls::ALL_CIPHERSUITES
+                .iter()
+                .filter(|suite| suite.usable_for_version(version))
+                .map(|suite| Suite {
+                    id: suite.suite.get_u16(),
+                    name: format!("{0:?}", suite.suite),
+                    rustls: true,
+                })
+                .collect(),
+            None => vec![],
+        };
+        if *self != TlsVersion::Tls13 {
+            let legacy = LEGACY_SUITES
+                .iter()
+                .filter(|(_, _, tls12_only)| *self == TlsVersion::Tls12 || !tls12_only)
+                .map(|(id, name, _)| Suite {
+                    id: *id,
+                    name: name.to_string(),
+                    rustls: false,
+                });
+            suites.extend(legacy);
+        }
+        suites
+    }
+}
+
+impl fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            TlsVersion::Tls10 => "TLSv1.0",
+            TlsVersion::Tls11 => "TLSv1.1",
+            TlsVersion::Tls12 => "TLSv1.2",
+            TlsVersion::Tls13 => "TLSv1.3",
+        };
+        f.pad(s)
+    }
+}
+
+impl FromStr for TlsVersion {
+    type Err = anyhow::Error;
+
+    /// Parse version like `1.2`, `tls1.2` or `TLSv1.2`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        let number = lower.trim_start_matches("tls").trim_start_matches('v');
+        match number {
+            "1.0" => Ok(TlsVersion::Tls10),
+            "1.1" => Ok(TlsVersion::Tls11),
+            "1.2" => Ok(TlsVersion::Tls12),
+            "1.3" => Ok(TlsVersion::Tls13),
+            _ => bail!("unsupported TLS version {0}", s),
+        }
+    }
+}
+
+/// Protocol versions and cipher suites a server is allowed to accept
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScanPolicy {
+    /// Oldest version allowed, TLS 1.2 by default
+    pub min_version: TlsVersion,
+    /// Whether cipher suites in CBC mode are allowed, false by default
+    pub allow_cbc: bool,
+}
+
+impl Default for ScanPolicy {
+    fn default() -> Self {
+        ScanPolicy {
+            min_version: TlsVersion::Tls12,
+            allow_cbc: false,
+        }
+    }
+}
+
+impl ScanPolicy {
+    /// Options accepted by server against policy
+    fn violations(&self, versions: &[VersionScan]) -> Vec<String> {
+        let mut violations = vec![];
+        for scanned in versions.iter().filter(|scanned| scanned.accepted) {
+            if scanned.version < self.min_version {
+                violations.push(format!("{0} accepted", scanned.version));
+            }
+            if self.allow_cbc {
+                continue;
+            }
+            for suite in scanned.suites.iter() {
+                if suite.accepted && suite.name.contains("_CBC_") {
+                    let s = format!("{0} accepted with {1}", suite.name, scanned.version);
+                    violations.push(s);
+                }
+            }
+        }
+        violations
+    }
+}
+
+impl fmt::Display for ScanPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{0}+ only", self.min_version)?;
+        if !self.allow_cbc {
+            write!(f, ", no CBC suites")?;
+        }
+        Ok(())
+    }
+}
+
+/// Cipher suite offered alone in handshake
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SuiteScan {
+    /// IANA name of cipher suite
+    pub name: String,
+    /// Whether server accepted it
+    pub accepted: bool,
+}
+
+/// Protocol version offered alone in handshake
+#[derive(Clone, Debug, PartialEq)]
+pub struct VersionScan {
+    /// Protocol version
+    pub version: TlsVersion,
+    /// Whether server accepted it with any cipher suite
+    pub accepted: bool,
+    /// Cipher suites offered with this version, empty if version is rejected
+    pub suites: Vec<SuiteScan>,
+}
+
+/// Protocol versions and cipher suites accepted by one target
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScanResult<'a> {
+    /// Protocol spoken before TLS handshake
+    pub protocol: Protocol,
+    /// Domain name of target
+    pub domain_name: &'a str,
+    /// Port of target
+    pub port: u16,
+    /// Policy which result is compared with
+    pub policy: String,
+    /// Versions from the newest, empty if scan failed
+    pub versions: Vec<VersionScan>,
+    /// Options accepted by server against policy
+    pub violations: Vec<String>,
+    /// Reason why scan failed
+    pub error: Option<ErrorKind>,
+}
+
+impl<'a> ScanResult<'a> {
+    /// Create a result from target which cannot be parsed
+    pub fn invalid_target(target: &'a str) -> Self {
+        ScanResult {
+            domain_name: target,
+            error: Some(ErrorKind::InvalidTarget),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn new(target: &Target<'a>, policy: &ScanPolicy) -> Self {
+        ScanResult {
+            protocol: target.protocol,
+            domain_name: target.host,
+            port: target.port,
+            policy: policy.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Record versions and compare them with policy
+    pub(crate) fn complete(&mut self, versions: Vec<VersionScan>, policy: &ScanPolicy) {
+        self.violations = policy.violations(&versions);
+        self.versions = versions;
+    }
+
+    /// Whether target is scanned and complies with policy
+    ///
+    /// ```
+    /// # use hcc::ScanResult;
+    /// let result = ScanResult::default();
+    /// assert!(result.is_compliant());
+    /// ```
+    pub fn is_compliant(&self) -> bool {
+        self.error.is_none() && self.violations.is_empty()
+    }
+
+    /// Target in the same format as it is given
+    pub fn target(&self) -> String {
+        // port is unknown when target cannot be parsed
+        if self.port == 0 {
+            return self.domain_name.to_string();
+        }
+        let target = Target {
+            protocol: self.protocol,
+            host: self.domain_name,
+            port: self.port,
+            address: None,
+        };
+        target.to_string()
+    }
+
+    /// Human-readable sentence of verdict
+    ///
+    /// ```
+    /// # use hcc::ScanResult;
+    /// let result = ScanResult::default();
+    /// result.sentence();
+    /// ```
+    pub fn sentence(&self) -> String {
+        let target = self.target();
+        if let Some(kind) = self.error {
+            return format!("failed to scan {0}: {1}", target, kind);
+        }
+        if self.violations.is_empty() {
+            return format!("{0} complies with {1}", target, self.policy);
+        }
+        let violations = self.violations.join(", ");
+        format!("{0} violates {1}: {2}", target, self.policy, violations)
+    }
+}
+
+impl<'a> fmt::Display for ScanResult<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let icon = match (self.error, self.violations.is_empty()) {
+            (Some(_), _) => "[!]",
+            (None, true) => "[v]",
+            (None, false) => "[x]",
+        };
+        write!(f, "{0} {1}", icon, self.sentence())?;
+        for scanned in self.versions.iter() {
+            let accepted = if scanned.accepted {
+                "accepted"
+            } else {
+                "rejected"
+            };
+            write!(f, "\n    {0:<50}{1}", scanned.version, accepted)?;
+            for suite in scanned.suites.iter() {
+                let accepted = if suite.accepted {
+                    "accepted"
+                } else {
+                    "rejected"
+                };
+                write!(f, "\n        {0:<46}{1}", suite.name, accepted)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Result of scan in JSON format
+#[derive(Default, Serialize, Deserialize)]
+pub struct ScanResultJSON {
+    /// Whether target is scanned and complies with policy
+    pub compliant: bool,
+    /// Protocol spoken before TLS handshake
+    pub protocol: String,
+    /// Domain name of target
+    pub domain_name: String,
+    /// Port of target
+    pub port: u16,
+    /// Policy which result is compared with
+    pub policy: String,
+    /// Versions from the newest
+    pub versions: Vec<VersionScanJSON>,
+    /// Options accepted by server against policy
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<String>,
+    /// Reason why scan failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Protocol version in scan result in JSON format
+#[derive(Default, Serialize, Deserialize)]
+pub struct VersionScanJSON {
+    /// Protocol version
+    pub version: String,
+    /// Whether server accepted it with any cipher suite
+    pub accepted: bool,
+    /// Cipher suites offered with this version, empty if version is rejected
+    pub suites: Vec<SuiteScan>,
+}
+
+impl ScanResultJSON {
+    /// Convert scan result into JSON format
+    ///
+    /// ```
+    /// # use hcc::{ScanResult, ScanResultJSON};
+    /// let result = ScanResult::default();
+    /// ScanResultJSON::new(&result);
+    /// ```
+    pub fn new(result: &ScanResult) -> Self {
+        ScanResultJSON {
+            compliant: result.is_compliant(),
+            protocol: result.protocol.to_string(),
+            domain_name: result.domain_name.to_string(),
+            port: result.port,
+            policy: result.policy.clone(),
+            versions: result
+                .versions
+                .iter()
+                .map(|scanned| VersionScanJSON {
+                    version: scanned.version.to_string(),
+                    accepted: scanned.accepted,
+                    suites: scanned.suites.clone(),
+                })
+                .collect(),
+            violations: result.violations.clone(),
+            error: result.error.map(|kind| kind.to_string()),
+        }
+    }
+}
+
+/// Offer cipher suites with one version in bare ClientHello, without TLS 1.3 extensions,
+/// whether server chooses the version and one of the suites in ServerHello.
+/// SNI is omitted without server name, e.g. for IP address
+pub(crate) async fn probe_hello<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    version: TlsVersion,
+    suites: &[u16],
+    server_name: Option<&str>,
+) -> io::Result<bool> {
+    stream
+        .write_all(&client_hello(version, suites, server_name))
+        .await?;
+    Ok(match server_hello(stream).await? {
+        Some((chosen, suite)) => chosen == version.wire() && suites.contains(&suite),
+        None => false,
+    })
+}
+
+/// ClientHello record offering cipher suites with one version up to TLS 1.2
+fn client_hello(version: TlsVersion, suites: &[u16], server_name: Option<&str>) -> Vec<u8> {
+    let mut extensions = vec![];
+    if let Some(name) = server_name {
+        let name = name.as_bytes();
+        let mut sni = u16_bytes(name.len() + 3).to_vec();
+        sni.push(0);
+        sni.extend_from_slice(&u16_bytes(name.len()));
+        sni.extend_from_slice(name);
+        push_extension(&mut extensions, 0x0000, &sni);
+    }
+    // x25519, secp256r1 and secp384r1
+    push_extension(&mut extensions, 0x000a, &[0, 6, 0, 0x1d, 0, 0x17, 0, 0x18]);
+    // uncompressed points
+    push_extension(&mut extensions, 0x000b, &[1, 0]);
+    if version == TlsVersion::Tls12 {
+        let schemes: &[u16] = &[
+            0x0403, 0x0503, 0x0603, 0x0804, 0x0805, 0x0806, 0x0401, 0x0501, 0x0601, 0x0201, 0x0203,
+        ];
+        let mut algorithms = u16_bytes(schemes.len() * 2).to_vec();
+        schemes
+            .iter()
+            .for_each(|scheme| algorithms.extend_from_slice(&u16_bytes(*scheme as usize)));
+        push_extension(&mut extensions, 0x000d, &algorithms);
+    }
+    // secure renegotiation
+    push_extension(&mut extensions, 0xff01, &[0]);
+
+    let mut body = u16_bytes(version.wire() as usize).to_vec();
+    body.extend_from_slice(&rand::random::<[u8; 32]>());
+    // empty session ID
+    body.push(0);
+    body.extend_from_slice(&u16_bytes(suites.len() * 2));
+    suites
+        .iter()
+        .for_each(|suite| body.extend_from_slice(&u16_bytes(*suite as usize)));
+    // null compression only
+    body.extend_from_slice(&[1, 0]);
+    body.extend_from_slice(&u16_bytes(extensions.len()));
+    body.extend_from_slice(&extensions);
+
+    let mut handshake = vec![1, 0];
+    handshake.extend_from_slice(&u16_bytes(body.len()));
+    handshake.extend_from_slice(&body);
+    // record version stays at TLS 1.0 for compatibility
+    let mut record = vec![22, 3, 1];
+    record.extend_from_slice(&u16_bytes(handshake.len()));
+    record.extend_from_slice(&handshake);
+    record
+}
+
+fn push_extension(extensions: &mut Vec<u8>, kind: u16, data: &[u8]) {
+    extensions.extend_from_slice(&u16_bytes(kind as usize));
+    extensions.extend_from_slice(&u16_bytes(data.len()));
+    extensions.extend_from_slice(data);
+}
+
+fn u16_bytes(n: usize) -> [u8; 2] {
+    (n as u16).to_be_bytes()
+}
+
+/// Version and cipher suite chosen in ServerHello, None if server sends alert or anything else
+async fn server_hello<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Option<(u16, u16)>> {
+    let mut header = [0; 5];
+    stream.read_exact(&mut header).await?;
+    if header[0] != 22 {
+        return Ok(None);
+    }
+    let mut record = vec![0; u16::from_be_bytes([header[3], header[4]]) as usize];
+    stream.read_exact(&mut record).await?;
+    // type, length, version, random and length of session ID
+    if record.len() < 39 || record[0] != 2 {
+        return Ok(None);
+    }
+    let version = u16::from_be_bytes([record[4], record[5]]);
+    let offset = 39 + record[38] as usize;
+    Ok(record
+        .get(offset..offset + 2)
+        .map(|suite| (version, u16::from_be_bytes([suite[0], suite[1]]))))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::scan::{
+        client_hello, server_hello, ScanPolicy, ScanResult, SuiteScan, TlsVersion, VersionScan,
+    };
+
+    #[test]
+    fn test_tls_version() {
+        assert_eq!(TlsVersion::Tls12, "1.2".parse().unwrap());
+        assert_eq!(TlsVersion::Tls13, "TLSv1.3".parse().unwrap());
+        assert_eq!(TlsVersion::Tls10, "tls1.0".parse().unwrap());
+        assert!("1.4".parse::<TlsVersion>().is_err());
+        assert!(TlsVersion::Tls11 < TlsVersion::Tls12);
+        assert_eq!("TLSv1.1", TlsVersion::Tls11.to_string());
+
+        let suites = TlsVersion::Tls13.suites();
+        assert!(suites
+            .iter()
+            .all(|s| s.rustls && s.name.starts_with("TLS13_")));
+        let suites = TlsVersion::Tls10.suites();
+        assert!(suites.iter().all(|s| !s.rustls && !s.name.contains("GCM")));
+        let suites = TlsVersion::Tls12.suites();
+        assert!(suites.iter().any(|s| s.rustls));
+        assert!(suites.iter().any(|s| s.id == 0xc013));
+    }
+
+    #[test]
+    fn test_client_hello() {
+        let hello = client_hello(TlsVersion::Tls11, &[0xc013, 0x002f], Some("example.com"));
+        assert_eq!([22, 3, 1], hello[..3]);
+        assert_eq!(
+            hello.len() - 5,
+            u16::from_be_bytes([hello[3], hello[4]]) as usize
+        );
+        // handshake type and client version
+        assert_eq!(1, hello[5]);
+        assert_eq!([3, 2], hello[9..11]);
+        // suites after random and empty session ID
+        assert_eq!([0, 4, 0xc0, 0x13, 0, 0x2f], hello[44..50]);
+        assert!(hello.windows(11).any(|w| w == b"example.com"));
+
+        let anonymous = client_hello(TlsVersion::Tls11, &[0xc013, 0x002f], None);
+        assert_eq!(hello.len() - anonymous.len(), 4 + 5 + 11);
+    }
+
+    #[tokio::test]
+    async fn test_server_hello() {
+        let mut record = vec![22, 3, 3, 0, 42, 2, 0, 0, 38, 3, 3];
+        record.extend_from_slice(&[0; 32]);
+        record.extend_from_slice(&[0, 0xc0, 0x2f, 0]);
+        let chosen = server_hello(&mut record.as_slice()).await.unwrap();
+        assert_eq!(Some((0x0303, 0xc02f)), chosen);
+
+        // handshake failure alert
+        let alert: &[u8] = &[21, 3, 3, 0, 2, 2, 40];
+        assert_eq!(None, server_hello(&mut &alert[..]).await.unwrap());
+
+        let truncated: &[u8] = &[22, 3, 3, 0, 10];
+        assert!(server_hello(&mut &truncated[..]).await.is_err());
+    }
+
+    #[test]
+    fn test_violations() {
+        let target = crate::Target::parse("example.com").unwrap();
+        let policy = ScanPolicy::default();
+        let versions = vec![
+            VersionScan {
+                version: TlsVersion::Tls12,
+                accepted: true,
+                suites: vec![
+                    SuiteScan {
+                        name: "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256".into(),
+                        accepted: true,
+                    },
+                    SuiteScan {
+                        name: "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA".into(),
+                        accepted: true,
+                    },
+                    SuiteScan {
+                        name: "TLS_RSA_WITH_AES_128_CBC_SHA".into(),
+                        accepted: false,
+                    },
+                ],
+            },
+            VersionScan {
+                version: TlsVersion::Tls10,
+                accepted: true,
+                suites: vec![],
+            },
+        ];
+        let mut result = ScanResult::new(&target, &policy);
+        result.complete(versions.clone(), &policy);
+        assert!(!result.is_compliant());
+        assert_eq!(
+            vec![
+                "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA accepted with TLSv1.2",
+                "TLSv1.0 accepted"
+            ],
+            result.violations
+        );
+        let s = result.to_string();
+        assert!(s.starts_with(
+            "[x] example.com:443 violates TLSv1.2+ only, no CBC suites: TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA accepted with TLSv1.2, TLSv1.0 accepted\n"
+        ));
+        assert!(s.contains("\n    TLSv1.2                                           accepted\n"));
+        assert!(s.contains("\n        TLS_RSA_WITH_AES_128_CBC_SHA                  rejected"));
+
+        let policy = ScanPolicy {
+            min_version: TlsVersion::Tls10,
+            allow_cbc: true,
+        };
+        let mut result = ScanResult::new(&target, &policy);
+        result.complete(versions, &policy);
+        assert!(result.is_compliant());
+        assert!(result
+            .to_string()
+            .starts_with("[v] example.com:443 complies with TLSv1.0+ only\n"));
+    }
+}
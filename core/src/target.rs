@@ -0,0 +1,250 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use anyhow::bail;
+
+use crate::protocol::Protocol;
+
+/// Host and port of an endpoint to be checked
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Target<'a> {
+    /// Protocol spoken before TLS handshake
+    pub protocol: Protocol,
+    /// Domain name sent as SNI and verified against certificate, or IP address
+    /// connected to without SNI
+    pub host: &'a str,
+    /// TCP port
+    pub port: u16,
+    /// IP address to connect to instead of resolving host
+    pub address: Option<IpAddr>,
+}
+
+impl<'a> Target<'a> {
+    /// Parse target from `host` or `host:port`,
+    /// optionally prefixed with protocol e.g. `smtp://`,
+    /// and optionally suffixed with IP address to connect to e.g. `@203.0.113.7`
+    ///
+    /// ```
+    /// # use hcc::{Protocol, Target};
+    /// let target = Target::parse("example.com:8443").unwrap();
+    /// assert_eq!("example.com", target.host);
+    /// assert_eq!(8443, target.port);
+    ///
+    /// let target = Target::parse("smtp://mail.example.com:587").unwrap();
+    /// assert_eq!(Protocol::Smtp, target.protocol);
+    /// assert_eq!("mail.example.com", target.host);
+    /// assert_eq!(587, target.port);
+    ///
+    /// let target = Target::parse("[2001:db8::1]:6443").unwrap();
+    /// assert_eq!("2001:db8::1", target.host);
+    /// assert_eq!(6443, target.port);
+    ///
+    /// let target = Target::parse("example.com@[2001:db8::1]").unwrap();
+    /// assert_eq!("example.com", target.host);
+    /// assert_eq!(Some("2001:db8::1".parse().unwrap()), target.address);
+    /// ```
+    pub fn parse(s: &'a str) -> anyhow::Result<Self> {
+        let s = s.trim();
+        let (protocol, s) = match s.find("://") {
+            Some(i) => (Protocol::from_str(&s[..i])?, &s[i + 3..]),
+            None => (Protocol::default(), s),
+        };
+        let (s, address) = match s.rfind('@') {
+            Some(i) => (&s[..i], Some(Self::parse_address(s, &s[i + 1..])?)),
+            None => (s, None),
+        };
+        let target = Self::parse_host_port(s, protocol)?;
+        Ok(Target { address, ..target })
+    }
+
+    fn parse_host_port(s: &'a str, protocol: Protocol) -> anyhow::Result<Self> {
+        let default_port = protocol.default_port();
+        if let Some(rest) = s.strip_prefix('[') {
+            let (host, rest) = match rest.find(']') {
+                Some(i) => (&rest[..i], &rest[i + 1..]),
+                None => bail!("missing closing bracket in {0}", s),
+            };
+            let port = if rest.is_empty() {
+                default_port
+            } else if let Some(port) = rest.strip_prefix(':') {
+                Self::parse_port(s, port)?
+            } else {
+                bail!("unexpected characters after bracket in {0}", s);
+            };
+            return Self::new(s, protocol, host, port);
+        }
+        match s.matches(':').count() {
+            0 => Self::new(s, protocol, s, default_port),
+            1 => {
+                let i = s.find(':').unwrap();
+                let port = Self::parse_port(s, &s[i + 1..])?;
+                Self::new(s, protocol, &s[..i], port)
+            }
+            // bare IPv6 address without brackets
+            _ => Self::new(s, protocol, s, default_port),
+        }
+    }
+
+    fn new(s: &str, protocol: Protocol, host: &'a str, port: u16) -> anyhow::Result<Self> {
+        if host.is_empty() {
+            bail!("host is missing in {0}", s);
+        }
+        Ok(Target {
+            protocol,
+            host,
+            port,
+            address: None,
+        })
+    }
+
+    /// Whether host is IP address, whose certificate is only checked for expiry
+    /// as there is no domain name to verify it against
+    pub(crate) fn is_ip_address(&self) -> bool {
+        self.host.parse::<IpAddr>().is_ok()
+    }
+
+    fn parse_address(s: &str, address: &str) -> anyhow::Result<IpAddr> {
+        let address = address
+            .strip_prefix('[')
+            .and_then(|address| address.strip_suffix(']'))
+            .unwrap_or(address);
+        match address.parse::<IpAddr>() {
+            Ok(address) => Ok(address),
+            Err(_) => bail!("invalid IP address in {0}", s),
+        }
+    }
+
+    fn parse_port(s: &str, port: &str) -> anyhow::Result<u16> {
+        match port.parse::<u16>() {
+            Ok(0) | Err(_) => bail!("invalid port in {0}", s),
+            Ok(port) => Ok(port),
+        }
+    }
+}
+
+impl<'a> fmt::Display for Target<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.protocol != Protocol::Https {
+            write!(f, "{0}://", self.protocol)?;
+        }
+        if self.host.contains(':') {
+            write!(f, "[{0}]:{1}", self.host, self.port)?;
+        } else {
+            write!(f, "{0}:{1}", self.host, self.port)?;
+        }
+        match self.address {
+            Some(IpAddr::V6(address)) => write!(f, "@[{0}]", address),
+            Some(address) => write!(f, "@{0}", address),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::protocol::Protocol;
+    use crate::target::Target;
+
+    #[test]
+    fn test_parse_domain_name() {
+        let target = Target::parse("example.com").unwrap();
+        assert_eq!("example.com", target.host);
+        assert_eq!(443, target.port);
+    }
+
+    #[test]
+    fn test_parse_domain_name_with_port() {
+        let target = Target::parse("example.com:8443").unwrap();
+        assert_eq!("example.com", target.host);
+        assert_eq!(8443, target.port);
+    }
+
+    #[test]
+    fn test_parse_ip_address() {
+        let target = Target::parse("[2001:db8::1]:6443").unwrap();
+        assert_eq!("2001:db8::1", target.host);
+        assert_eq!(6443, target.port);
+        assert!(target.is_ip_address());
+
+        let target = Target::parse("[2001:db8::1]").unwrap();
+        assert_eq!("2001:db8::1", target.host);
+        assert_eq!(443, target.port);
+
+        let target = Target::parse("2001:db8::1").unwrap();
+        assert_eq!("2001:db8::1", target.host);
+        assert_eq!(443, target.port);
+
+        let target = Target::parse("smtp://203.0.113.7:587").unwrap();
+        assert_eq!("203.0.113.7", target.host);
+        assert_eq!(587, target.port);
+        assert!(target.is_ip_address());
+        assert!(!Target::parse("example.com").unwrap().is_ip_address());
+    }
+
+    #[test]
+    fn test_parse_protocol() {
+        let target = Target::parse("imap://mail.example.com").unwrap();
+        assert_eq!(Protocol::Imap, target.protocol);
+        assert_eq!("mail.example.com", target.host);
+        assert_eq!(143, target.port);
+
+        let target = Target::parse("xmpp://example.com:5223").unwrap();
+        assert_eq!(Protocol::Xmpp, target.protocol);
+        assert_eq!("example.com", target.host);
+        assert_eq!(5223, target.port);
+
+        let target = Target::parse("https://example.com").unwrap();
+        assert_eq!(Protocol::Https, target.protocol);
+        assert_eq!(443, target.port);
+
+        assert!(Target::parse("gopher://example.com").is_err());
+    }
+
+    #[test]
+    fn test_parse_address() {
+        let target = Target::parse("example.com@203.0.113.7").unwrap();
+        assert_eq!("example.com", target.host);
+        assert_eq!(443, target.port);
+        assert_eq!(Some("203.0.113.7".parse().unwrap()), target.address);
+
+        let target = Target::parse("smtp://mail.example.com:587@[2001:db8::1]").unwrap();
+        assert_eq!(Protocol::Smtp, target.protocol);
+        assert_eq!("mail.example.com", target.host);
+        assert_eq!(587, target.port);
+        assert_eq!(Some("2001:db8::1".parse().unwrap()), target.address);
+
+        let target = Target::parse("example.com").unwrap();
+        assert_eq!(None, target.address);
+
+        assert!(Target::parse("example.com@").is_err());
+        assert!(Target::parse("example.com@example.net").is_err());
+        assert!(Target::parse("@203.0.113.7").is_err());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(Target::parse("").is_err());
+        assert!(Target::parse(":443").is_err());
+        assert!(Target::parse("example.com:").is_err());
+        assert!(Target::parse("example.com:0").is_err());
+        assert!(Target::parse("example.com:65536").is_err());
+        assert!(Target::parse("[::1").is_err());
+        assert!(Target::parse("[::1]443").is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let target = Target::parse("example.com").unwrap();
+        assert_eq!("example.com:443", target.to_string());
+
+        let target = Target::parse("pop3://mail.example.com").unwrap();
+        assert_eq!("pop3://mail.example.com:110", target.to_string());
+
+        let target = Target::parse("[::1]").unwrap();
+        assert_eq!("[::1]:443", target.to_string());
+
+        let target = Target::parse("example.com:8443@2001:db8::1").unwrap();
+        assert_eq!("example.com:8443@[2001:db8::1]", target.to_string());
+    }
+}
@@ -0,0 +1,145 @@
+//! Rules deciding when a valid certificate should be renewed
+use std::str::FromStr;
+
+use anyhow::bail;
+
+use crate::certificate::CertificateInfo;
+
+/// When a valid certificate is due for renewal, by fixed days before expiry,
+/// by fraction of its lifetime elapsed, or by whichever comes first
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Threshold {
+    /// Due when certificate expires in this many days or fewer
+    pub days: Option<i64>,
+    /// Due when this fraction of `not_before..not_after` has elapsed, e.g. 2/3 like certbot
+    pub lifetime_fraction: Option<f64>,
+}
+
+impl Threshold {
+    /// Whether any rule is reached by certificate at timestamp
+    pub(crate) fn is_reached(&self, certificate: &CertificateInfo, now: i64) -> bool {
+        let by_days = matches!(self.days, Some(days) if certificate.days <= days);
+        let lifetime = (certificate.not_after - certificate.not_before) as f64;
+        let elapsed = (now - certificate.not_before) as f64;
+        let by_fraction = matches!(
+            self.lifetime_fraction,
+            Some(fraction) if lifetime > 0.0 && elapsed >= fraction * lifetime
+        );
+        by_days || by_fraction
+    }
+}
+
+impl FromStr for Threshold {
+    type Err = anyhow::Error;
+
+    /// Parse rules separated by comma, days like `14d` and fraction of lifetime
+    /// like `2/3`, `67%` or `0.67`
+    ///
+    /// ```
+    /// # use hcc::Threshold;
+    /// let threshold: Threshold = "7d,2/3".parse().unwrap();
+    /// assert_eq!(Some(7), threshold.days);
+    /// assert_eq!(Some(2.0 / 3.0), threshold.lifetime_fraction);
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut threshold = Threshold::default();
+        for rule in s.split(',').map(str::trim) {
+            if let Some(days) = rule.strip_suffix('d') {
+                match days.parse() {
+                    Ok(days) if threshold.days.is_none() => threshold.days = Some(days),
+                    _ => bail!("expected one number of days like 14d in {0}", s),
+                }
+            } else if threshold.lifetime_fraction.is_none() {
+                threshold.lifetime_fraction = Some(parse_fraction(rule)?);
+            } else {
+                bail!("expected one fraction of lifetime in {0}", s);
+            }
+        }
+        Ok(threshold)
+    }
+}
+
+/// Parse fraction of lifetime like `2/3`, `67%` or `0.67`, between 0 and 1
+pub(crate) fn parse_fraction(s: &str) -> anyhow::Result<f64> {
+    let fraction = if let Some((numerator, denominator)) = s.split_once('/') {
+        numerator
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .zip(denominator.trim().parse::<f64>().ok())
+            .map(|(numerator, denominator)| numerator / denominator)
+    } else if let Some(percent) = s.strip_suffix('%') {
+        percent
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .map(|percent| percent / 100.0)
+    } else {
+        s.parse().ok()
+    };
+    match fraction {
+        Some(fraction) if fraction > 0.0 && fraction <= 1.0 => Ok(fraction),
+        _ => bail!(
+            "expected fraction of lifetime like 2/3, 67% or 0.67 in {0}",
+            s
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, Utc};
+
+    use crate::certificate::CertificateInfo;
+    use crate::fixtures::TestServer;
+    use crate::threshold::{parse_fraction, Threshold};
+
+    #[test]
+    fn test_parse_threshold() {
+        let threshold: Threshold = "14d".parse().unwrap();
+        assert_eq!(Some(14), threshold.days);
+        assert_eq!(None, threshold.lifetime_fraction);
+        let threshold: Threshold = "50%, 30d".parse().unwrap();
+        assert_eq!(Some(30), threshold.days);
+        assert_eq!(Some(0.5), threshold.lifetime_fraction);
+
+        assert_eq!(0.25, parse_fraction("1/4").unwrap());
+        assert_eq!(0.67, parse_fraction("0.67").unwrap());
+        assert!(parse_fraction("3/2").is_err());
+        assert!(parse_fraction("0%").is_err());
+        assert!("7d,14d".parse::<Threshold>().is_err());
+        assert!("2/3,50%".parse::<Threshold>().is_err());
+        assert!("soon".parse::<Threshold>().is_err());
+    }
+
+    #[test]
+    fn test_is_reached() {
+        let now = Utc::now();
+        // 60 of 90 days elapsed, expires in 30 days
+        let server =
+            TestServer::with_intermediate(now - Duration::days(60), now + Duration::days(30));
+        let chain = server.chain();
+        let intermediate = CertificateInfo::parse(&chain[1].0, &now).unwrap();
+        let now = now.timestamp();
+
+        let days = |days| Threshold {
+            days: Some(days),
+            lifetime_fraction: None,
+        };
+        let fraction = |fraction| Threshold {
+            days: None,
+            lifetime_fraction: Some(fraction),
+        };
+        assert!(days(30).is_reached(&intermediate, now));
+        assert!(!days(7).is_reached(&intermediate, now));
+        assert!(fraction(0.5).is_reached(&intermediate, now));
+        assert!(!fraction(0.75).is_reached(&intermediate, now));
+        assert!(!Threshold::default().is_reached(&intermediate, now));
+
+        let either = Threshold {
+            days: Some(7),
+            lifetime_fraction: Some(0.5),
+        };
+        assert!(either.is_reached(&intermediate, now));
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{TimeZone, Utc};
+use rustls::{Certificate, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError};
+
+use crate::certificate::CertificateInfo;
+
+/// Signature algorithms accepted by rustls
+static SUPPORTED_SIG_ALGS: &[&webpki::SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::ED25519,
+    &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA384_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA512_LEGACY_KEY,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+
+/// Verifier which records presented certificates before verifying them,
+/// so that certificates are available even when handshake fails
+pub(crate) struct RecordingVerifier {
+    chain: Mutex<Vec<Certificate>>,
+    clock_skew: Duration,
+    error: Mutex<Option<TLSError>>,
+    expiry_only: bool,
+    /// Whether certificate is verified against name given in handshake
+    check_name: bool,
+    ocsp_response: Mutex<Vec<u8>>,
+}
+
+impl RecordingVerifier {
+    /// Verifier which tolerates certificate not valid yet by clock skew.
+    /// In expiry only mode, handshake always continues and error is recorded instead
+    pub(crate) fn new(clock_skew: Duration, expiry_only: bool) -> Self {
+        RecordingVerifier {
+            chain: Mutex::new(vec![]),
+            clock_skew,
+            error: Mutex::new(None),
+            expiry_only,
+            check_name: true,
+            ocsp_response: Mutex::new(vec![]),
+        }
+    }
+
+    /// Verifier in expiry only mode for IP address hosts, which records whether
+    /// certificates are trusted regardless of name
+    pub(crate) fn for_ip_address(clock_skew: Duration) -> Self {
+        RecordingVerifier {
+            check_name: false,
+            ..RecordingVerifier::new(clock_skew, true)
+        }
+    }
+
+    /// Certificates presented by server
+    pub(crate) fn take_chain(&self) -> Vec<Certificate> {
+        std::mem::take(&mut *self.chain.lock().unwrap())
+    }
+
+    /// Reason why certificates are not trusted in expiry only mode
+    pub(crate) fn take_error(&self) -> Option<TLSError> {
+        self.error.lock().unwrap().take()
+    }
+
+    /// OCSP response stapled by server, empty if server does not staple
+    pub(crate) fn take_ocsp_response(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.ocsp_response.lock().unwrap())
+    }
+
+    /// Verify certificates at time when every certificate in chain is valid,
+    /// to find out whether certificates are trusted regardless of dates
+    fn verify_trust(
+        roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        dns_name: Option<webpki::DNSNameRef>,
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, TLSError> {
+        let chain: Vec<CertificateInfo> = presented_certs
+            .iter()
+            .filter_map(|c| CertificateInfo::parse(c.as_ref(), &Utc.timestamp(0, 0)))
+            .collect();
+        let not_before = chain.iter().map(|c| c.not_before).max();
+        let not_after = chain.iter().map(|c| c.not_after).min();
+        let time = match (not_before, not_after) {
+            (Some(not_before), Some(not_after)) if not_before < not_after => {
+                let now = now
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs() as i64);
+                now.max(not_before).min(not_after - 1)
+            }
+            _ => return Self::verify_at(roots, presented_certs, dns_name, now),
+        };
+        let time = UNIX_EPOCH + Duration::from_secs(time.max(0) as u64);
+        Self::verify_at(roots, presented_certs, dns_name, time)
+    }
+
+    /// Verify certificates like rustls does, at given time, and against name when it is given
+    fn verify_at(
+        roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        dns_name: Option<webpki::DNSNameRef>,
+        time: SystemTime,
+    ) -> Result<ServerCertVerified, TLSError> {
+        let (leaf, intermediates) = match presented_certs.split_first() {
+            Some((leaf, intermediates)) => (leaf, intermediates),
+            None => return Err(TLSError::NoCertificatesPresented),
+        };
+        let cert = webpki::EndEntityCert::from(&leaf.0).map_err(TLSError::WebPKIError)?;
+        let intermediates: Vec<&[u8]> = intermediates.iter().map(|c| c.0.as_ref()).collect();
+        let anchors: Vec<webpki::TrustAnchor> =
+            roots.roots.iter().map(|r| r.to_trust_anchor()).collect();
+        let time = webpki::Time::try_from(time).map_err(|_| TLSError::FailedToGetCurrentTime)?;
+        cert.verify_is_valid_tls_server_cert(
+            SUPPORTED_SIG_ALGS,
+            &webpki::TLSServerTrustAnchors(&anchors),
+            &intermediates,
+            time,
+        )
+        .map_err(TLSError::WebPKIError)?;
+        if let Some(dns_name) = dns_name {
+            cert.verify_is_valid_for_dns_name(dns_name)
+                .map_err(TLSError::WebPKIError)?;
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        dns_name: webpki::DNSNameRef,
+        ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        *self.chain.lock().unwrap() = presented_certs.to_vec();
+        *self.ocsp_response.lock().unwrap() = ocsp_response.to_vec();
+        let now = SystemTime::now();
+        let dns_name = Some(dns_name).filter(|_| self.check_name);
+        if self.expiry_only {
+            // dates are reported by client, so only trust is recorded
+            if let Err(e) = Self::verify_trust(roots, presented_certs, dns_name, now) {
+                *self.error.lock().unwrap() = Some(e);
+            }
+            return Ok(ServerCertVerified::assertion());
+        }
+        match Self::verify_at(roots, presented_certs, dns_name, now) {
+            // verify again as if clock of server was behind
+            Err(TLSError::WebPKIError(webpki::Error::CertNotValidYet))
+                if self.clock_skew > Duration::ZERO =>
+            {
+                let time = now + self.clock_skew;
+                Self::verify_at(roots, presented_certs, dns_name, time)
+            }
+            result => result,
+        }
+    }
+}
@@ -1,12 +1,14 @@
 #![forbid(unsafe_code)]
+use std::path::PathBuf;
+use std::time::Duration;
 
 use log::info;
-use serde::Serialize;
 use structopt::StructOpt;
 
 use actix_web::{get, middleware, web, App, HttpResponse, HttpServer};
 use env_logger::Env;
-use hcc::{CheckClient, CheckResultJSON};
+use hcc::{CheckClient, CheckClientBuilder, CheckResultJSON};
+use serde::Deserialize;
 
 #[derive(Debug, StructOpt)]
 #[structopt(author, about)]
@@ -14,37 +16,54 @@ struct Opts {
     /// host:port to be bound to the server
     #[structopt(short, long, default_value = "127.0.0.1:9292")]
     bind: String,
+    /// Timeout in seconds to check one domain name
+    #[structopt(short, long, default_value = "30")]
+    timeout: u64,
+    /// PEM bundle of extra certificate authorities to trust
+    #[structopt(long, number_of_values = 1)]
+    ca_file: Vec<PathBuf>,
+    /// Directory of PEM files of extra certificate authorities to trust
+    #[structopt(long, number_of_values = 1)]
+    ca_dir: Vec<PathBuf>,
+    /// Trust certificate authorities of operating system
+    #[structopt(long)]
+    native_roots: bool,
 }
 
-#[derive(Serialize)]
-struct ErrorMessage {
-    message: String,
+struct AppState {
+    builder: CheckClientBuilder,
 }
 
-struct AppState {
-    client: CheckClient,
+#[derive(Debug, Deserialize)]
+struct Thresholds {
+    /// Warn when certificate expires in this many days or fewer
+    warning: Option<i64>,
+    /// Report certificate as critical when it expires in this many days or fewer
+    critical: Option<i64>,
 }
 
-#[get("/{domain_names}")]
+#[get("/{domain_names:.+}")]
 async fn show_domain_name(
     data: web::Data<AppState>,
-    web::Path((domain_names,)): web::Path<(String,)>,
+    domain_names: web::Path<String>,
+    thresholds: web::Query<Thresholds>,
 ) -> HttpResponse {
+    let mut builder = data.builder.clone();
+    if let Some(days) = thresholds.warning {
+        builder.grace_in_days(days);
+    }
+    if let Some(days) = thresholds.critical {
+        builder.critical_in_days(days);
+    }
+    let client = builder.build();
     let domain_names: Vec<&str> = domain_names.split(',').map(|s| s.trim()).collect();
-    let results = match data.client.check_certificates(domain_names.as_slice()) {
-        Ok(r) => r,
-        Err(e) => {
-            return HttpResponse::InternalServerError().json(&ErrorMessage {
-                message: format!("{:?}", e),
-            });
-        }
-    };
+    let results = client.check_certificates(domain_names.as_slice()).await;
     if results.len() == 1 {
         let json = CheckResultJSON::new(results.first().unwrap());
-        HttpResponse::Ok().json(&json)
+        HttpResponse::Ok().json(json)
     } else {
-        let json: Vec<CheckResultJSON> = results.iter().map(|r| CheckResultJSON::new(&r)).collect();
-        HttpResponse::Ok().json(&json)
+        let json: Vec<CheckResultJSON> = results.iter().map(|r| CheckResultJSON::new(r)).collect();
+        HttpResponse::Ok().json(json)
     }
 }
 
@@ -53,9 +72,20 @@ async fn main() -> anyhow::Result<()> {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
 
     let opts: Opts = Opts::from_args();
-    let data = web::Data::new(AppState {
-        client: CheckClient::builder().elapsed(true).build(),
-    });
+    let mut builder = CheckClient::builder();
+    for path in opts.ca_file.iter() {
+        builder.ca_file(path)?;
+    }
+    for path in opts.ca_dir.iter() {
+        builder.ca_dir(path)?;
+    }
+    if opts.native_roots {
+        builder.native_roots()?;
+    }
+    builder
+        .elapsed(true)
+        .timeout(Duration::from_secs(opts.timeout));
+    let data = web::Data::new(AppState { builder });
 
     info!("Served on {0}", &opts.bind);
     HttpServer::new(move || {